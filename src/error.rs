@@ -0,0 +1,63 @@
+//! Errors of project generation
+
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// `cargo metadata` failed
+    CargoMetadata(cargo_metadata::Error),
+    /// `[package.metadata.xcode]` in `Cargo.toml` can't be used
+    InvalidMetadata { manifest_path: PathBuf, message: String },
+    /// Project or target name that can't be used as a file name in Xcode
+    InvalidTargetName { name: String, reason: &'static str },
+    /// Xcode project files are UTF-8
+    NonUtf8Path(PathBuf),
+    /// Path can't be made relative to the Xcode project's dir
+    NoRelativePath { path: PathBuf, base: PathBuf },
+    /// Reading sources or writing project files failed
+    Io { path: PathBuf, error: io::Error },
+    /// Package chosen by name isn't a member of the workspace
+    PackageNotFound { name: String },
+    /// Binary target chosen by name isn't in the selected packages
+    BinNotFound { name: String },
+}
+
+impl Error {
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |error| Self::Io { path: path.to_owned(), error }
+    }
+}
+
+/// Underlying errors are in `source()`
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CargoMetadata(_) => f.write_str("can't read Cargo metadata. Check that `cargo metadata` works in this directory, or use --manifest-path"),
+            Self::InvalidMetadata { manifest_path, message } => write!(f, "invalid [package.metadata.xcode] in {}: {message}", manifest_path.display()),
+            Self::InvalidTargetName { name, reason } => write!(f, "'{name}' can't be used as a project name, because {reason}. Use --project-name to choose another name"),
+            Self::NonUtf8Path(path) => write!(f, "path {} is not UTF-8, which Xcode project files can't contain", path.display()),
+            Self::NoRelativePath { path, base } => write!(f, "{} can't be referenced relative to {}. Use an absolute --output-dir", path.display(), base.display()),
+            Self::Io { path, .. } => write!(f, "can't access {}", path.display()),
+            Self::PackageNotFound { name } => write!(f, "package '{name}' is not a member of the workspace"),
+            Self::BinNotFound { name } => write!(f, "no bin target named '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CargoMetadata(e) => Some(e),
+            Self::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<cargo_metadata::Error> for Error {
+    fn from(e: cargo_metadata::Error) -> Self {
+        Self::CargoMetadata(e)
+    }
+}
@@ -1,22 +1,42 @@
 //! cargo-xcode is meant to be used from command line. See [CLI usage docs](https://lib.rs/cargo-xcode).
+//!
+//! Build tools can use [`Generator::from_metadata`] with [`GeneratorOptions`] to generate projects the same way as the CLI.
 
-use cargo_metadata::Package;
+use cargo_metadata::{Metadata, Package, Target};
 use crc::{Crc, CRC_64_ECMA_182};
-use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::{fs, process};
+
+mod error;
+mod metadata;
+mod options;
+mod platforms;
+mod report;
+mod xcconfig;
+
+pub use error::Error;
+pub use options::GeneratorOptions;
+pub use report::{ProjectReport, TargetReport};
+use metadata::{Configuration, TargetDirPolicy, XcodeMetadata};
+use xcconfig::BuildSetting;
 
 struct XcodeTarget {
     kind: String,
+    cargo_target_name: String,
     base_name: String,
     cargo_file_name: String,
     xcode_product_name: String,
     xcode_file_name: String,
     compiler_flags: String,
+    /// Space-separated, in addition to package-wide features
+    features: String,
     file_type: &'static str,
     prod_type: &'static str,
-    supported_platforms: &'static str,
+    supported_platforms: String,
     skip_install: bool,
+    /// Dylib uses `@rpath` install name for embedding in app bundles
+    embeddable: bool,
 }
 
 struct XcodeObject {
@@ -36,20 +56,61 @@ pub struct Generator {
     crc: Crc<u64>,
     id_base: u64,
     package: Package,
-    output_dir: Option<PathBuf>,
-    custom_project_name: Option<String>,
+    options: GeneratorOptions,
+    workspace_target_dir: Option<PathBuf>,
+    workspace_root: Option<PathBuf>,
 }
 
 const STATIC_LIB_APPLE_PRODUCT_TYPE: &str = "com.apple.product-type.library.static";
 const DY_LIB_APPLE_PRODUCT_TYPE: &str = "com.apple.product-type.library.dynamic";
 const EXECUTABLE_APPLE_PRODUCT_TYPE: &str = "com.apple.product-type.tool";
 
+const SHARED_XCCONFIG_FILE_NAME: &str = "CargoXcode.xcconfig";
+const INPUT_FILE_LIST_FILE_NAME: &str = "CargoInputs.xcfilelist";
+/// First line of generated xcconfig files, which tells them apart from files made by hand
+const XCCONFIG_HEADER: &str = "// generated with cargo-xcode";
+
 impl Generator {
     pub fn new(package: Package, output_dir: Option<PathBuf>, custom_project_name: Option<String>) -> Self {
+        let mut options = GeneratorOptions::new();
+        options.output_dir = output_dir;
+        options.project_name = custom_project_name;
+        Self::with_options(package, options)
+    }
+
+    pub fn with_options(package: Package, options: GeneratorOptions) -> Self {
         let crc = Crc::<u64>::new(&CRC_64_ECMA_182);
-        let id_base = crc.checksum(package.id.repr.as_bytes());
+        let id_base = crc.checksum(options.id_seed.as_ref().unwrap_or(&package.id.repr).as_bytes());
 
-        Self { crc, id_base, package, output_dir, custom_project_name }
+        Self { crc, id_base, package, options, workspace_target_dir: None, workspace_root: None }
+    }
+
+    /// Generators for workspace members that have any targets usable in Xcode
+    pub fn from_metadata(metadata: &Metadata, options: GeneratorOptions) -> Vec<Self> {
+        metadata.workspace_packages().into_iter()
+            // other targets are kept, because build scripts are inputs of the build
+            .filter(|package| options.is_package_included(package))
+            .filter(|package| package.targets.iter().any(|t| is_supported_target(t, &options) && options.is_included(t)))
+            .map(|package| {
+                Self::with_options(package.clone(), options.clone())
+                    .with_workspace_target_dir(metadata.target_directory.clone().into_std_path_buf())
+                    .with_workspace_root(metadata.workspace_root.clone().into_std_path_buf())
+            })
+            .collect()
+    }
+
+    /// Cargo's target dir of the workspace, needed for the `target-dir = "workspace"` setting
+    #[must_use]
+    pub fn with_workspace_target_dir(mut self, target_dir: PathBuf) -> Self {
+        self.workspace_target_dir = Some(target_dir);
+        self
+    }
+
+    /// Dir of the workspace's `Cargo.toml`, which has the `Cargo.lock` (default: the package's dir)
+    #[must_use]
+    pub fn with_workspace_root(mut self, root: PathBuf) -> Self {
+        self.workspace_root = Some(root);
+        self
     }
 
     fn make_id(&self, kind: &str, name: &str) -> String {
@@ -64,21 +125,92 @@ impl Generator {
         out
     }
 
-    pub fn write_pbxproj(&self) -> Result<PathBuf, io::Error> {
-        let proj_path = self.prepare_project_path()?;
-        let proj_data = self.pbxproj()?;
+    pub fn write_pbxproj(&self) -> Result<PathBuf, Error> {
+        self.write_project().map(|report| report.path)
+    }
+
+    /// Like `write_pbxproj`, but also tells what has been written
+    pub fn write_project(&self) -> Result<ProjectReport, Error> {
+        let files = self.project_files()?;
+        let mut report = self.report()?;
+        fs::create_dir_all(&report.path).map_err(Error::io(&report.path))?;
 
-        let pbx_path = proj_path.join("project.pbxproj");
-        let mut f = fs::File::create(pbx_path)?;
-        f.write_all(proj_data.as_bytes())?;
+        for (path, contents) in files {
+            if write_if_changed(&path, &contents)? {
+                report.files_written.push(path);
+            }
+        }
+        // the new pbxproj doesn't reference them any more
+        for path in self.stale_files()? {
+            fs::remove_file(&path).map_err(Error::io(&path))?;
+            report.files_removed.push(path);
+        }
+        Ok(report)
+    }
 
-        Ok(proj_path)
+    /// Paths and contents of all files inside the `.xcodeproj` dir, without writing them.
+    /// `project.pbxproj` is last, so that Xcode doesn't reload the project before the files it references exist.
+    pub fn project_files(&self) -> Result<Vec<(PathBuf, String)>, Error> {
+        let proj_path = self.project_path()?;
+        let mut files = self.xcconfig_files()?.into_iter().map(|(file_name, contents)| (proj_path.join(file_name), contents)).collect::<Vec<_>>();
+        files.push((proj_path.join(INPUT_FILE_LIST_FILE_NAME), self.input_file_list()?));
+        files.push((proj_path.join("project.pbxproj"), self.pbxproj()?));
+        Ok(files)
     }
 
-    fn project_targets(&self) -> Vec<XcodeTarget> {
-        self.package.targets.iter().flat_map(|target| {
-            let base_name = self.custom_project_name.as_ref().unwrap_or(&target.name).clone();
+    /// Generated xcconfig files inside the `.xcodeproj` dir of configurations that don't exist any more
+    pub fn stale_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let proj_path = self.project_path()?;
+        let entries = match fs::read_dir(&proj_path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::io(&proj_path)(e)),
+        };
+        let current = self.xcconfig_files()?.into_iter().map(|(file_name, _)| proj_path.join(file_name)).collect::<Vec<_>>();
+        let mut stale = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(Error::io(&proj_path))?.path();
+            if path.extension().is_some_and(|ext| ext == "xcconfig") && !current.contains(&path)
+                && fs::read_to_string(&path).is_ok_and(|c| c.starts_with(XCCONFIG_HEADER)) {
+                stale.push(path);
+            }
+        }
+        stale.sort();
+        Ok(stale)
+    }
+
+    /// Project and targets that are generated
+    pub fn report(&self) -> Result<ProjectReport, Error> {
+        let xcode_metadata = self.xcode_metadata()?;
+        Ok(ProjectReport {
+            package: self.package.name.clone(),
+            path: self.project_path()?,
+            project_id: self.make_id("", "<project>"),
+            targets: self.project_targets(&xcode_metadata).into_iter().map(|t| {
+                let product_id = self.make_id(t.file_type, &t.cargo_file_name);
+                TargetReport {
+                    name: format!("{}-{}", t.base_name, t.kind),
+                    target_id: self.make_id(t.file_type, &product_id),
+                    product_id,
+                    cargo_target: t.cargo_target_name,
+                    kind: t.kind,
+                    product_name: t.xcode_product_name,
+                    file_name: t.xcode_file_name,
+                    cargo_file_name: t.cargo_file_name,
+                    product_type: t.prod_type.into(),
+                    supported_platforms: t.supported_platforms.split_whitespace().map(String::from).collect(),
+                }
+            }).collect(),
+            files_written: Vec::new(),
+            files_removed: Vec::new(),
+        })
+    }
+
+    fn project_targets(&self, xcode_metadata: &XcodeMetadata) -> Vec<XcodeTarget> {
+        self.package.targets.iter().filter(|t| self.options.is_included(t)).flat_map(|target| {
+            let base_name = self.options.project_name.as_ref().unwrap_or(&target.name).clone();
             let required_features = target.required_features.join(",");
+            let features = xcode_metadata.target_features.get(&target.name).map(|f| f.join(" ")).unwrap_or_default();
             target.kind.iter().filter_map(move |kind| {
             let (cargo_file_name, xcode_file_name, xcode_product_name, file_type, prod_type, skip_install) = match kind.as_str() {
                 "bin" => (target.name.clone(), base_name.clone(),  base_name.clone(), "compiled.mach-o.executable", EXECUTABLE_APPLE_PRODUCT_TYPE, false),
@@ -89,27 +221,36 @@ impl Generator {
                 },
                 _ => return None,
             };
+            // a target that can't be built anywhere would only fail in Xcode
+            let supported_platforms = supported_platforms(kind, &self.options).join(" ");
+            if supported_platforms.is_empty() {
+                return None;
+            }
 
-            let mut compiler_flags = if prod_type == EXECUTABLE_APPLE_PRODUCT_TYPE { format!("--bin '{base_name}'") } else { "--lib".into() };
+            // the build script parses these as shell words
+            let mut compiler_flags = if prod_type == EXECUTABLE_APPLE_PRODUCT_TYPE { format!("--bin {}", shell_quote(&target.name)) } else { "--lib".into() };
             if prod_type == EXECUTABLE_APPLE_PRODUCT_TYPE && !required_features.is_empty() {
-                compiler_flags.push_str(&format!(" --features '{required_features}'")); // Xcode escapes \=
+                compiler_flags.push_str(&format!(" --features {}", shell_quote(&required_features)));
             }
 
             Some(XcodeTarget {
                 kind: kind.to_owned(),
+                cargo_target_name: target.name.clone(),
                 compiler_flags,
-                supported_platforms: if prod_type == STATIC_LIB_APPLE_PRODUCT_TYPE { "macosx iphonesimulator iphoneos appletvsimulator appletvos" } else { "macosx" },
+                features: features.clone(),
+                supported_platforms,
                 base_name: base_name.clone(),
                 cargo_file_name, xcode_file_name,
                 xcode_product_name,
                 file_type,
                 prod_type,
-                skip_install,
+                skip_install: skip_install || (prod_type == DY_LIB_APPLE_PRODUCT_TYPE && xcode_metadata.embed_dylib),
+                embeddable: prod_type == DY_LIB_APPLE_PRODUCT_TYPE && xcode_metadata.embed_dylib,
             })
         })}).collect()
     }
 
-    fn products_pbxproj(&self, cargo_targets: &[XcodeTarget], manifest_path_id: &str, build_rule_id: &str, lipo_script_id: &str) -> XcodeSections {
+    fn products_pbxproj(&self, cargo_targets: &[XcodeTarget], configurations: &[Configuration], code_sign_flags: &str, manifest_path_id: &str, build_rule_id: &str, lipo_script_id: &str) -> XcodeSections {
         let mut other = Vec::new();
         let mut targets = Vec::new();
         let mut product_ids = Vec::new();
@@ -120,15 +261,16 @@ impl Generator {
             let prod_id = self.make_id(target.file_type, &target.cargo_file_name);
             let target_id = self.make_id(target.file_type, &prod_id);
             let conf_list_id = self.make_id("<config-list>", &prod_id);
-            let conf_release_id = self.make_id("<config-release>", &prod_id);
-            let conf_debug_id = self.make_id("<config-debug>", &prod_id);
+            let conf_ids = configurations.iter()
+                .map(|c| (self.make_id(&format!("<config-{}>", c.name.to_lowercase()), &prod_id), c))
+                .collect::<Vec<_>>();
             let compile_cargo_id = self.make_id("<cargo>", &prod_id);
             let manifest_path_build_object_id = self.make_id("<cargo-toml>", &prod_id);
 
             targets.push(XcodeObject {
                 id: target_id.clone(),
                 def: format!(
-                    r##"{target_id} /* {base_name}-{kind} */ = {{
+                    r##"{target_id} /* {comment} */ = {{
             isa = PBXNativeTarget;
             buildConfigurationList = {conf_list_id};
             buildPhases = (
@@ -140,16 +282,16 @@ impl Generator {
             );
             dependencies = (
             );
-            name = "{base_name}-{kind}";
+            name = "{name}";
             productName = "{xcode_file_name}";
             productReference = {prod_id};
             productType = "{prod_type}";
         }};
         "##,
-                    base_name = target.base_name,
+                    comment = plist_comment(&format!("{}-{}", target.base_name, target.kind)),
+                    name = plist_escape(&format!("{}-{}", target.base_name, target.kind)),
                     prod_type = target.prod_type,
-                    xcode_file_name = target.xcode_file_name,
-                    kind = target.kind,
+                    xcode_file_name = plist_escape(&target.xcode_file_name),
                 ),
             });
 
@@ -178,7 +320,7 @@ impl Generator {
                     }};
                 }};
                 "#,
-                    compiler_flags = target.compiler_flags,
+                    compiler_flags = plist_escape(&escape_dollars(&target.compiler_flags)),
                 ),
             });
 
@@ -189,13 +331,13 @@ impl Generator {
         {conf_list_id} /* {kind} */ = {{
             isa = XCConfigurationList;
             buildConfigurations = (
-                {conf_release_id} /* Release */,
-                {conf_debug_id} /* Debug */,
+                {conf_refs}
             );
             defaultConfigurationIsVisible = 0;
             defaultConfigurationName = Release;
         }};"##,
                     kind = target.kind,
+                    conf_refs = configuration_refs(&conf_ids),
                 ),
             });
 
@@ -208,33 +350,51 @@ impl Generator {
             } else {
                 ""
             };
-            let dylib_flags = if target.prod_type == DY_LIB_APPLE_PRODUCT_TYPE && self.package.version.major != 1 {
+            let features_flags = if target.features.is_empty() {
+                String::new()
+            } else {
+                format!("CARGO_XCODE_TARGET_FEATURES = \"{}\";", plist_escape(&target.features))
+            };
+            let mut dylib_flags = if target.prod_type == DY_LIB_APPLE_PRODUCT_TYPE && self.package.version.major != 1 {
                 format!("DYLIB_COMPATIBILITY_VERSION = \"{}\";", self.package.version.major)
             } else {
                 String::new()
             };
+            // the app only needs to "Embed & Sign" it, and have the Frameworks dir in its runpath (Xcode's templates do)
+            if target.embeddable {
+                dylib_flags.push_str(r#"
+                    DYLIB_INSTALL_NAME_BASE = "@rpath";
+                    LD_RUNPATH_SEARCH_PATHS = "@executable_path/../Frameworks @loader_path/../Frameworks";"#);
+            }
 
-            other.extend([(conf_release_id, "Release"), (conf_debug_id, "Debug")].iter().map(|(id, name)| XcodeObject {
+            let code_sign_flags = if target.prod_type == STATIC_LIB_APPLE_PRODUCT_TYPE { "" } else { code_sign_flags };
+
+            other.extend(conf_ids.iter().map(|(id, conf)| XcodeObject {
                 id: id.to_owned(),
                 def: format!(
                     r##"
             {id} /* {kind} */ = {{
                 isa = XCBuildConfiguration;
+                baseConfigurationReference = {xcconfig_id} /* {xcconfig_comment} */;
                 buildSettings = {{
                     PRODUCT_NAME = "{xcode_product_name}";
                     "CARGO_XCODE_CARGO_FILE_NAME" = "{cargo_file_name}";
                     "CARGO_XCODE_CARGO_DEP_FILE_NAME" = "{dep_file_name}";
                     SUPPORTED_PLATFORMS = "{supported_platforms}";
                     {skip_install_flags}
-                    {dylib_flags}
+                    {features_flags}
+                    {dylib_flags}{code_sign_flags}
                 }};
-                name = {name};
+                name = "{name}";
             }};"##,
+                    name = plist_escape(&conf.name),
+                    xcconfig_id = self.xcconfig_id(conf),
+                    xcconfig_comment = plist_comment(&conf.xcconfig_file_name()),
                     kind = target.kind,
-                    cargo_file_name = target.cargo_file_name,
-                    dep_file_name = Path::new(&target.cargo_file_name).with_extension("d").file_name().unwrap().to_str().unwrap(),
-                    xcode_product_name = target.xcode_product_name,
-                    supported_platforms = target.supported_platforms,
+                    cargo_file_name = plist_escape(&escape_dollars(&target.cargo_file_name)),
+                    dep_file_name = plist_escape(&escape_dollars(&Path::new(&target.cargo_file_name).with_extension("d").to_string_lossy())),
+                    xcode_product_name = plist_escape(&escape_dollars(&target.xcode_product_name)),
+                    supported_platforms = plist_escape(&target.supported_platforms),
                 ),
             }));
 
@@ -252,7 +412,7 @@ impl Generator {
             sourceTree = TARGET_BUILD_DIR;
         }};"##,
                     kind = target.kind,
-                    xcode_file_name = target.xcode_file_name,
+                    xcode_file_name = plist_escape(&target.xcode_file_name),
                     file_type = target.file_type
                 ),
             });
@@ -262,20 +422,26 @@ impl Generator {
         }
     }
 
-    pub fn pbxproj(&self) -> Result<String, io::Error> {
+    pub fn pbxproj(&self) -> Result<String, Error> {
+        check_project_name(self.project_name())?;
+        let xcode_metadata = self.xcode_metadata()?;
         let main_group_id = self.make_id("", "<root>");
         let prod_group_id = self.make_id("", "Products");
+        let xcconfig_group_id = self.make_id("", "Configuration");
         let frameworks_group_id = self.make_id("", "Frameworks"); // This is a magic name that Xcode uses to show Products
         let project_id = self.make_id("", "<project>");
         let build_rule_id = self.make_id("", "BuildRule");
         let lipo_script_id = self.make_id("", "LipoScript");
         let conf_list_id = self.make_id("", "<configuration-list>");
-        let conf_release_id = self.make_id("configuration", "Release");
-        let conf_debug_id = self.make_id("configuration", "Debug");
+        let configurations = xcode_metadata.configurations();
+        let conf_ids = configurations.iter()
+            .map(|c| (self.make_id("configuration", &c.name), c))
+            .collect::<Vec<_>>();
         let manifest_path_id = self.make_id("", "Cargo.toml");
 
-        let rust_targets = self.project_targets();
-        let mut sections = self.products_pbxproj(&rust_targets, &manifest_path_id, &build_rule_id, &lipo_script_id);
+        let rust_targets = self.project_targets(&xcode_metadata);
+        let code_sign_flags = self.code_sign_flags(&xcode_metadata)?;
+        let mut sections = self.products_pbxproj(&rust_targets, &configurations, &code_sign_flags, &manifest_path_id, &build_rule_id, &lipo_script_id);
 
         let product_refs = sections.product_ids.iter().map(|id| format!("{id},\n")).collect::<String>();
         let target_refs = sections.targets.iter().map(|o| format!("{},\n", o.id)).collect::<String>();
@@ -295,10 +461,7 @@ impl Generator {
 
         main_folder_refs.push(manifest_path_id.clone());
 
-        let cargo_toml_path = match &self.output_dir {
-            Some(output_dir) => pathdiff::diff_paths(&self.package.manifest_path, output_dir).unwrap(),
-            None => "Cargo.toml".into(),
-        };
+        let cargo_toml_path = relative_path(self.package.manifest_path.as_std_path(), &self.project_dir())?;
 
         sections.filereference.push(XcodeObject {
             id: manifest_path_id.clone(),
@@ -312,10 +475,34 @@ impl Generator {
                     path = "{cargo_toml_path}";
                     sourceTree = "<group>";
             }};"#,
-                cargo_toml_path = cargo_toml_path.display(),
+                cargo_toml_path = plist_escape(&cargo_toml_path),
             ),
         });
 
+        let proj_file_name = self.project_file_name();
+        let mut xcconfig_refs = String::new();
+        for file_name in configurations.iter().map(|c| c.xcconfig_file_name()).chain([SHARED_XCCONFIG_FILE_NAME.to_string()]) {
+            let id = self.make_id("xcconfig", &file_name);
+            let comment = plist_comment(&file_name);
+            xcconfig_refs.push_str(&format!("{id} /* {comment} */,\n"));
+            sections.filereference.push(XcodeObject {
+                id: id.clone(),
+                def: format!(
+                    r#"
+                {id} /* {comment} */ = {{
+                    isa = PBXFileReference;
+                    lastKnownFileType = text.xcconfig;
+                    name = "{name}";
+                    path = "{path}";
+                    sourceTree = "<group>";
+            }};"#,
+                    name = plist_escape(&file_name),
+                    path = plist_escape(&format!("{proj_file_name}/{file_name}")),
+                ),
+            });
+        }
+
+        main_folder_refs.push(xcconfig_group_id.clone());
         main_folder_refs.push(prod_group_id.clone());
         main_folder_refs.push(frameworks_group_id.clone());
 
@@ -326,71 +513,190 @@ impl Generator {
         let main_folder_refs = main_folder_refs.iter().map(|id| format!("{id},\n")).collect::<String>();
 
         let build_script = r##"
-set -eu; export PATH="$HOME/.cargo/bin:$PATH:/usr/local/bin";
+set -eu;
+# CARGO_XCODE_EXTRA_PATH is for toolchains that aren't in the usual places (Nix, mise, etc.)
+export PATH="${CARGO_XCODE_EXTRA_PATH:+$CARGO_XCODE_EXTRA_PATH:}$HOME/.cargo/bin:$PATH:/usr/local/bin";
+if [ -n "${CARGO_XCODE_ENV:-}" ]; then
+    eval "export $CARGO_XCODE_ENV"
+fi
 if [ "${IS_MACCATALYST-NO}" = YES ]; then
-    CARGO_XCODE_TARGET_TRIPLE="${CARGO_XCODE_TARGET_ARCH}-apple-ios-macabi"
+    CARGO_XCODE_TARGET_TRIPLE="${CARGO_XCODE_MACCATALYST_TARGET_TRIPLE:-}"
+    CARGO_XCODE_BUILD_STD="${CARGO_XCODE_MACCATALYST_BUILD_STD:-}"
+    CARGO_XCODE_PANIC="${CARGO_XCODE_MACCATALYST_PANIC:-${CARGO_XCODE_PANIC:-}}"
+fi
+if [ -z "${CARGO_XCODE_TARGET_TRIPLE:-}" ]; then
+    echo "error: cargo-xcode doesn't know which Rust target to use for $PLATFORM_NAME $CURRENT_ARCH. Set CARGO_XCODE_TARGET_TRIPLE"
+    exit 1
+fi
+CARGO_XCODE_TARGET_FLAGS="--target=${CARGO_XCODE_TARGET_TRIPLE}"
+# one cargo invocation builds all archs in parallel. Invocations for other archs wait for Cargo's lock, and find their build done.
+if [ "${CARGO_XCODE_BUILD_ALL_ARCHS:-NO}" = YES ]; then
+    if [ "${IS_MACCATALYST-NO}" = YES ]; then
+        CARGO_XCODE_TARGET_TRIPLES="${CARGO_XCODE_MACCATALYST_TARGET_TRIPLES:-}"
+    fi
+    set -f # arch patterns are globs
+    for CARGO_XCODE_ARCH in ${ARCHS}; do
+        for CARGO_XCODE_ARCH_TRIPLE in ${CARGO_XCODE_TARGET_TRIPLES:-}; do # arch-pattern:triple
+            case "$CARGO_XCODE_ARCH" in ${CARGO_XCODE_ARCH_TRIPLE%%:*})
+                case " $CARGO_XCODE_TARGET_FLAGS " in *" --target=${CARGO_XCODE_ARCH_TRIPLE#*:} "*) ;;
+                    *) CARGO_XCODE_TARGET_FLAGS="$CARGO_XCODE_TARGET_FLAGS --target=${CARGO_XCODE_ARCH_TRIPLE#*:}";;
+                esac
+                break;;
+            esac
+        done
+    done
+    set +f
+fi
+CARGO_XCODE_USE_NIGHTLY=""
+CARGO_XCODE_BUILD_FLAGS=""
+# follow sanitizers and code coverage enabled in Xcode's scheme
+CARGO_XCODE_RUSTFLAGS=""
+CARGO_XCODE_RUSTFLAGS_DIR="rustflags"
+if [ "${ENABLE_ADDRESS_SANITIZER:-NO}" = YES ]; then
+    CARGO_XCODE_RUSTFLAGS="$CARGO_XCODE_RUSTFLAGS -Zsanitizer=address"
+    CARGO_XCODE_RUSTFLAGS_DIR="$CARGO_XCODE_RUSTFLAGS_DIR-asan"
     CARGO_XCODE_USE_NIGHTLY="+nightly"
-    CARGO_XCODE_BUILD_FLAGS="-Z build-std=panic_abort,std"
-else
-    CARGO_XCODE_TARGET_TRIPLE="${CARGO_XCODE_TARGET_ARCH}-apple-${CARGO_XCODE_TARGET_OS}"
-    CARGO_XCODE_USE_NIGHTLY=""
-    CARGO_XCODE_BUILD_FLAGS=""
 fi
-if [ "$CARGO_XCODE_TARGET_OS" != "darwin" ]; then
+if [ "${ENABLE_THREAD_SANITIZER:-NO}" = YES ]; then
+    CARGO_XCODE_RUSTFLAGS="$CARGO_XCODE_RUSTFLAGS -Zsanitizer=thread"
+    CARGO_XCODE_RUSTFLAGS_DIR="$CARGO_XCODE_RUSTFLAGS_DIR-tsan"
+    CARGO_XCODE_BUILD_STD="${CARGO_XCODE_BUILD_STD:-std,panic_abort}" # uninstrumented std causes false positives
+fi
+if [ "${CLANG_COVERAGE_MAPPING:-NO}" = YES ]; then
+    CARGO_XCODE_RUSTFLAGS="$CARGO_XCODE_RUSTFLAGS -Cinstrument-coverage"
+    CARGO_XCODE_RUSTFLAGS_DIR="$CARGO_XCODE_RUSTFLAGS_DIR-cov"
+fi
+# LLVM bitcode in the static lib lets Xcode's linker optimize across Rust and Swift/ObjC
+if [ "${CARGO_XCODE_LINKER_PLUGIN_LTO:-NO}" = YES ] && [ "$MACH_O_TYPE" = staticlib ]; then
+    CARGO_XCODE_RUSTFLAGS="$CARGO_XCODE_RUSTFLAGS -Clinker-plugin-lto -Cembed-bitcode=yes"
+    CARGO_XCODE_RUSTFLAGS_DIR="$CARGO_XCODE_RUSTFLAGS_DIR-lto"
+fi
+if [ -n "$CARGO_XCODE_RUSTFLAGS" ]; then
+    if [ -n "${RUSTFLAGS:-}" ]; then # it already replaces rustflags from Cargo's config
+        export RUSTFLAGS="$RUSTFLAGS$CARGO_XCODE_RUSTFLAGS"
+    else
+        # merged with build.rustflags from .cargo/config.toml, which RUSTFLAGS would replace
+        CARGO_XCODE_RUSTFLAGS_TOML=""
+        for CARGO_XCODE_RUSTFLAG in $CARGO_XCODE_RUSTFLAGS; do
+            CARGO_XCODE_RUSTFLAGS_TOML="${CARGO_XCODE_RUSTFLAGS_TOML:+$CARGO_XCODE_RUSTFLAGS_TOML,}'$CARGO_XCODE_RUSTFLAG'"
+        done
+        CARGO_XCODE_BUILD_FLAGS="$CARGO_XCODE_BUILD_FLAGS --config=build.rustflags=[$CARGO_XCODE_RUSTFLAGS_TOML]"
+    fi
+    # changed rustflags would invalidate the regular build
+    export CARGO_TARGET_DIR="$CARGO_TARGET_DIR/$CARGO_XCODE_RUSTFLAGS_DIR"
+fi
+if [ -n "${CARGO_XCODE_BUILD_STD:-}" ]; then # tier 3 targets don't have a pre-built std
+    CARGO_XCODE_USE_NIGHTLY="+nightly"
+    CARGO_XCODE_BUILD_FLAGS="$CARGO_XCODE_BUILD_FLAGS -Z build-std=${CARGO_XCODE_BUILD_STD}"
+fi
+if [ -n "${CARGO_XCODE_PANIC:-}" ]; then
+    CARGO_XCODE_BUILD_FLAGS="$CARGO_XCODE_BUILD_FLAGS --config=profile.dev.panic='${CARGO_XCODE_PANIC}' --config=profile.release.panic='${CARGO_XCODE_PANIC}'"
+fi
+if [[ "$CARGO_XCODE_TARGET_TRIPLE" != *-apple-darwin ]]; then
     PATH="${PATH/\/Contents\/Developer\/Toolchains\/XcodeDefault.xctoolchain\/usr\/bin:/xcode-provided-ld-cant-link-lSystem-for-the-host-build-script:}"
 fi
 PATH="$PATH:/opt/homebrew/bin" # Rust projects often depend on extra tools like nasm, which Xcode lacks
-if [ "$CARGO_XCODE_BUILD_MODE" == release ]; then
-    OTHER_INPUT_FILE_FLAGS="${OTHER_INPUT_FILE_FLAGS} --release"
+if [ -n "${CARGO_XCODE_CARGO_PATH:-}" ] && [ -x "$CARGO_XCODE_CARGO_PATH" ]; then
+    CARGO_XCODE_CARGO="$CARGO_XCODE_CARGO_PATH"
+    PATH="$(dirname "$CARGO_XCODE_CARGO_PATH"):$PATH" # rustc is usually installed next to cargo
+    if [ -n "$CARGO_XCODE_USE_NIGHTLY" ]; then # only rustup's proxy understands +toolchain
+        echo "warning: this build needs nightly Rust, but CARGO_XCODE_CARGO_PATH=$CARGO_XCODE_CARGO_PATH can't switch toolchains. It must be a nightly cargo"
+        CARGO_XCODE_USE_NIGHTLY=""
+    fi
+elif command -v cargo &> /dev/null; then
+    if [ -n "${CARGO_XCODE_CARGO_PATH:-}" ]; then
+        echo "warning: CARGO_XCODE_CARGO_PATH=$CARGO_XCODE_CARGO_PATH is not executable, using cargo from PATH"
+    fi
+    CARGO_XCODE_CARGO="cargo"
+else
+    echo "error: cargo not found. Set CARGO_XCODE_CARGO_PATH to the absolute path of cargo, or add its directory to CARGO_XCODE_EXTRA_PATH. PATH=$PATH"
+    exit 1
 fi
-if command -v rustup &> /dev/null; then
-    if ! rustup target list --installed | egrep -q "${CARGO_XCODE_TARGET_TRIPLE}"; then
-        echo "warning: this build requires rustup toolchain for $CARGO_XCODE_TARGET_TRIPLE, but it isn't installed"
-        # rustup target add "${CARGO_XCODE_TARGET_TRIPLE}" || echo >&2 "warning: can't install $CARGO_XCODE_TARGET_TRIPLE"
+# rustc reads the same deployment target env vars as Xcode, but Xcode doesn't always export them
+for CARGO_XCODE_DEPLOYMENT_TARGET in MACOSX_DEPLOYMENT_TARGET IPHONEOS_DEPLOYMENT_TARGET TVOS_DEPLOYMENT_TARGET WATCHOS_DEPLOYMENT_TARGET XROS_DEPLOYMENT_TARGET; do
+    if [ -n "${!CARGO_XCODE_DEPLOYMENT_TARGET:-}" ]; then
+        export "$CARGO_XCODE_DEPLOYMENT_TARGET"
     fi
+done
+CARGO_XCODE_PROFILE_FLAGS="--profile=${CARGO_XCODE_BUILD_PROFILE}"
+if [ -n "${CARGO_XCODE_DEBUG_INFO:-}" ]; then
+    CARGO_XCODE_BUILD_FLAGS="$CARGO_XCODE_BUILD_FLAGS --config=profile.${CARGO_XCODE_BUILD_PROFILE}.debug='${CARGO_XCODE_DEBUG_INFO}'"
+fi
+if [ "${DEBUG_INFORMATION_FORMAT:-dwarf}" = dwarf-with-dsym ]; then
+    # dsymutil in the lipo phase needs the debug info left in object files
+    CARGO_XCODE_BUILD_FLAGS="$CARGO_XCODE_BUILD_FLAGS --config=profile.${CARGO_XCODE_BUILD_PROFILE}.split-debuginfo='unpacked'"
+fi
+CARGO_XCODE_FEATURE_FLAGS=""
+if [ "${CARGO_XCODE_NO_DEFAULT_FEATURES:-NO}" = YES ]; then
+    CARGO_XCODE_FEATURE_FLAGS="--no-default-features"
+fi
+if [ "${CARGO_XCODE_ALL_FEATURES:-NO}" = YES ]; then
+    CARGO_XCODE_FEATURE_FLAGS="$CARGO_XCODE_FEATURE_FLAGS --all-features"
+fi
+if [ -z "${CARGO_XCODE_BUILD_STD:-}" ] && command -v rustup &> /dev/null; then
+    for CARGO_XCODE_INSTALL_TRIPLE in ${CARGO_XCODE_TARGET_FLAGS//--target=/}; do
+        if ! rustup target list --installed | grep -qxF "${CARGO_XCODE_INSTALL_TRIPLE}"; then
+            echo "warning: this build requires rustup toolchain for $CARGO_XCODE_INSTALL_TRIPLE, but it isn't installed"
+            # rustup target add "${CARGO_XCODE_INSTALL_TRIPLE}" || echo >&2 "warning: can't install $CARGO_XCODE_INSTALL_TRIPLE"
+        fi
+    done
 fi
+eval "set -- ${CARGO_XCODE_EXTRA_ARGS:-}"
+# from target's COMPILER_FLAGS, shell-quoted
+eval "CARGO_XCODE_INPUT_FILE_FLAGS=(${OTHER_INPUT_FILE_FLAGS:-})"
 if [ "$ACTION" = clean ]; then
- ( set -x; cargo $CARGO_XCODE_USE_NIGHTLY clean $CARGO_XCODE_BUILD_FLAGS --manifest-path="$SCRIPT_INPUT_FILE" ${OTHER_INPUT_FILE_FLAGS} --target="${CARGO_XCODE_TARGET_TRIPLE}"; );
+ # in a shared target dir, clean only this package, without deleting other projects' builds
+ ( set -x; "$CARGO_XCODE_CARGO" $CARGO_XCODE_USE_NIGHTLY clean $CARGO_XCODE_BUILD_FLAGS --manifest-path="$SCRIPT_INPUT_FILE" ${CARGO_XCODE_CLEAN_PACKAGE:+--package="$CARGO_XCODE_CLEAN_PACKAGE"} $CARGO_XCODE_PROFILE_FLAGS --target="${CARGO_XCODE_TARGET_TRIPLE}"; );
 else
- ( set -x; cargo $CARGO_XCODE_USE_NIGHTLY build $CARGO_XCODE_BUILD_FLAGS --manifest-path="$SCRIPT_INPUT_FILE" --features="${CARGO_XCODE_FEATURES:-}" ${OTHER_INPUT_FILE_FLAGS} --target="${CARGO_XCODE_TARGET_TRIPLE}"; );
+ ( set -x; "$CARGO_XCODE_CARGO" $CARGO_XCODE_USE_NIGHTLY build $CARGO_XCODE_BUILD_FLAGS --manifest-path="$SCRIPT_INPUT_FILE" $CARGO_XCODE_FEATURE_FLAGS --features="${CARGO_XCODE_FEATURES:-} ${CARGO_XCODE_TARGET_FEATURES:-}" ${CARGO_XCODE_INPUT_FILE_FLAGS[@]+"${CARGO_XCODE_INPUT_FILE_FLAGS[@]}"} $CARGO_XCODE_PROFILE_FLAGS $CARGO_XCODE_TARGET_FLAGS "$@"; );
 fi
 # it's too hard to explain Cargo's actual exe path to Xcode build graph, so hardlink to a known-good path instead
 BUILT_SRC="${CARGO_TARGET_DIR}/${CARGO_XCODE_TARGET_TRIPLE}/${CARGO_XCODE_BUILD_MODE}/${CARGO_XCODE_CARGO_FILE_NAME}"
 ln -f -- "$BUILT_SRC" "$SCRIPT_OUTPUT_FILE_0"
 
-# xcode generates dep file, but for its own path, so append our rename to it
+# Xcode reruns this rule when files in the dep file change. Cargo's dep file is for its own path, lacks Cargo.lock and build.rs,
+# and doesn't exist before the first build, so the inputs are kept in a list that starts with files known when the project was generated
 DEP_FILE_SRC="${CARGO_TARGET_DIR}/${CARGO_XCODE_TARGET_TRIPLE}/${CARGO_XCODE_BUILD_MODE}/${CARGO_XCODE_CARGO_DEP_FILE_NAME}"
-if [ -f "$DEP_FILE_SRC" ]; then
-    DEP_FILE_DST="${DERIVED_FILE_DIR}/${CARGO_XCODE_TARGET_ARCH}-${EXECUTABLE_NAME}.d"
-    cp -f "$DEP_FILE_SRC" "$DEP_FILE_DST"
-
-    echo >> "$DEP_FILE_DST" "$(echo "$SCRIPT_OUTPUT_FILE_0" | sed 's/ /\\ /g'): $(echo "$BUILT_SRC" | sed 's/ /\\ /g')"
+# per arch, because Xcode runs the rule for all archs in parallel, and one could read the list while another is writing it
+INPUT_FILE_LIST="${DERIVED_FILE_DIR}/${CURRENT_ARCH}-${EXECUTABLE_NAME}-inputs.xcfilelist"
+if [ -f "$DEP_FILE_SRC" ] || [ ! -f "$INPUT_FILE_LIST" ]; then
+    {
+        if [ -f "${CARGO_XCODE_INPUT_FILE_LIST:-}" ]; then
+            # expand the same way as Xcode, without letting sed interpret the path
+            CARGO_XCODE_DOLLAR='$'
+            while IFS= read -r CARGO_XCODE_INPUT; do
+                CARGO_XCODE_INPUT="${CARGO_XCODE_INPUT//\$(DOLLAR)/$CARGO_XCODE_DOLLAR}"
+                case "$CARGO_XCODE_INPUT" in
+                    '$(PROJECT_DIR)'*) printf '%s\n' "${PROJECT_DIR}${CARGO_XCODE_INPUT#'$(PROJECT_DIR)'}";;
+                    *) printf '%s\n' "$CARGO_XCODE_INPUT";;
+                esac
+            done < "$CARGO_XCODE_INPUT_FILE_LIST"
+        fi
+        if [ -f "$DEP_FILE_SRC" ]; then
+            awk '{ sub(/^[^:]*: */, ""); gsub(/\\ /, "\001"); n = split($0, f, " "); for (i = 1; i <= n; i++) { gsub("\001", " ", f[i]); print f[i] } }' "$DEP_FILE_SRC"
+        fi
+    } | sort -u > "$INPUT_FILE_LIST"
 fi
+DEP_FILE_DST="${DERIVED_FILE_DIR}/${CURRENT_ARCH}-${EXECUTABLE_NAME}.d"
+CARGO_XCODE_DEP_ESCAPE='s/[ #]/\\&/g; s/\$/$$/g' # Makefile syntax
+{
+    printf '%s:' "$(printf '%s' "$SCRIPT_OUTPUT_FILE_0" | sed "$CARGO_XCODE_DEP_ESCAPE")"
+    { printf '%s\n' "$BUILT_SRC"; cat "$INPUT_FILE_LIST"; } | sed -e "$CARGO_XCODE_DEP_ESCAPE" -e 's/^/ /' | tr -d '\n'
+    echo
+} > "$DEP_FILE_DST"
 
-# lipo script needs to know all the platform-specific files that have been built
+# must match input for LipoScript. It's rewritten after every build to make Xcode rerun lipo.
 # archs is in the file name, so that paths don't stay around after archs change
-# must match input for LipoScript
 FILE_LIST="${DERIVED_FILE_DIR}/${ARCHS}-${EXECUTABLE_NAME}.xcfilelist"
-touch "$FILE_LIST"
-if ! egrep -q "$SCRIPT_OUTPUT_FILE_0" "$FILE_LIST" ; then
-    echo >> "$FILE_LIST" "$SCRIPT_OUTPUT_FILE_0"
-fi
+for CARGO_XCODE_ARCH in ${ARCHS}; do
+    printf '%s\n' "${OBJECT_FILE_DIR}/${CARGO_XCODE_ARCH}-${EXECUTABLE_NAME}"
+done > "$FILE_LIST.$CURRENT_ARCH.tmp"
+mv -f "$FILE_LIST.$CURRENT_ARCH.tmp" "$FILE_LIST" # other archs may be writing it at the same time
 "##.escape_default();
 
         let common_build_settings = format!(r##"
             ALWAYS_SEARCH_USER_PATHS = NO;
             SUPPORTS_MACCATALYST = YES;
-            CARGO_TARGET_DIR = "$(PROJECT_TEMP_DIR)/cargo_target"; /* for cargo */
-            CARGO_XCODE_FEATURES = ""; /* configure yourself */
-            "CARGO_XCODE_TARGET_ARCH[arch=arm64*]" = "aarch64";
-            "CARGO_XCODE_TARGET_ARCH[arch=x86_64*]" = "x86_64"; /* catalyst adds h suffix */
-            "CARGO_XCODE_TARGET_ARCH[arch=i386]" = "i686";
-            "CARGO_XCODE_TARGET_OS[sdk=macosx*]" = "darwin";
-            "CARGO_XCODE_TARGET_OS[sdk=iphonesimulator*]" = "ios-sim";
-            "CARGO_XCODE_TARGET_OS[sdk=iphonesimulator*][arch=x86_64*]" = "ios";
-            "CARGO_XCODE_TARGET_OS[sdk=iphoneos*]" = "ios";
-            "CARGO_XCODE_TARGET_OS[sdk=appletvsimulator*]" = "tvos";
-            "CARGO_XCODE_TARGET_OS[sdk=appletvos*]" = "tvos";
             PRODUCT_NAME = "{product_name}";
             MARKETING_VERSION = "{product_version}";
             CURRENT_PROJECT_VERSION = "{major}.{minor}";
@@ -398,15 +704,50 @@ fi
         "##,
             major = self.package.version.major,
             minor = self.package.version.minor,
-            product_name = self.package.name, // used as a base for output filename in Xcode
-            product_version = self.package.version.to_string(),
+            product_name = plist_escape(&self.package.name), // used as a base for output filename in Xcode
+            product_version = plist_escape(&self.package.version.to_string()),
         );
 
+        let conf_refs = configuration_refs(&conf_ids);
+        let configurations = conf_ids.iter().map(|(id, conf)| format!(
+            r##"
+        {id} = {{
+            isa = XCBuildConfiguration;
+            baseConfigurationReference = {xcconfig_id} /* {xcconfig_comment} */;
+            buildSettings = {{
+                {common_build_settings}
+            }};
+            name = "{name}";
+        }};
+"##,
+            name = plist_escape(&conf.name),
+            xcconfig_id = self.xcconfig_id(conf),
+            xcconfig_comment = plist_comment(&conf.xcconfig_file_name()),
+        )).collect::<String>();
+
         let lipo_script = r##"
-            set -eux; cat "$DERIVED_FILE_DIR/$ARCHS-$EXECUTABLE_NAME.xcfilelist" | tr '\n' '\0' | xargs -0 lipo -create -output "$TARGET_BUILD_DIR/$EXECUTABLE_PATH"
+            set -eu
+            # same paths as outputs of the build rule, for every arch
+            set --
+            for ARCH in $ARCHS; do
+                set -- "$@" "$OBJECT_FILE_DIR/$ARCH-$EXECUTABLE_NAME"
+            done
+            set -x
+            lipo -create -output "$TARGET_BUILD_DIR/$EXECUTABLE_PATH" "$@"
             if [ ${LD_DYLIB_INSTALL_NAME:+1} ]; then
                 install_name_tool -id "$LD_DYLIB_INSTALL_NAME" "$TARGET_BUILD_DIR/$EXECUTABLE_PATH"
             fi
+            # Cargo did the linking, so Xcode hasn't added these
+            if [ "$MACH_O_TYPE" != staticlib ]; then
+                for RPATH in ${LD_RUNPATH_SEARCH_PATHS:-}; do
+                    install_name_tool -add_rpath "$RPATH" "$TARGET_BUILD_DIR/$EXECUTABLE_PATH"
+                done
+            fi
+            # static libs get their debug info into the dSYM of the app that links them
+            if [ "${DEBUG_INFORMATION_FORMAT:-dwarf}" = dwarf-with-dsym ] && [ "$MACH_O_TYPE" != staticlib ]; then
+                mkdir -p "$DWARF_DSYM_FOLDER_PATH"
+                dsymutil "$TARGET_BUILD_DIR/$EXECUTABLE_PATH" -o "$DWARF_DSYM_FOLDER_PATH/$DWARF_DSYM_FILE_NAME"
+            fi
         "##.escape_default();
 
         let tpl = format!(
@@ -426,14 +767,17 @@ fi
         {build_rule_id} /* PBXBuildRule */ = {{
             isa = PBXBuildRule;
             compilerSpec = com.apple.compilers.proxy.script;
-            dependencyFile = "$(DERIVED_FILE_DIR)/$(CARGO_XCODE_TARGET_ARCH)-$(EXECUTABLE_NAME).d";
+            dependencyFile = "$(DERIVED_FILE_DIR)/$(CURRENT_ARCH)-$(EXECUTABLE_NAME).d";
             filePatterns = "*/Cargo.toml"; /* must contain asterisk */
             fileType = pattern.proxy;
+            inputFileListPaths = (
+                "$(CARGO_XCODE_INPUT_FILE_LIST)",
+            );
             inputFiles = ();
             isEditable = 0;
             name = "Cargo project build";
             outputFiles = (
-                "$(OBJECT_FILE_DIR)/$(CARGO_XCODE_TARGET_ARCH)-$(EXECUTABLE_NAME)",
+                "$(OBJECT_FILE_DIR)/$(CURRENT_ARCH)-$(EXECUTABLE_NAME)",
             );
             script = "# generated with cargo-xcode {crate_version}\n{build_script}";
         }};
@@ -444,6 +788,15 @@ fi
 /* End PBXFileReference section */
 
 /* Begin PBXGroup section */
+        {xcconfig_group_id} /* Configuration */ = {{
+            isa = PBXGroup;
+            children = (
+                {xcconfig_refs}
+            );
+            name = Configuration;
+            sourceTree = "<group>";
+        }};
+
         {frameworks_group_id} /* Frameworks */ = {{
             isa = PBXGroup;
             children = (
@@ -498,31 +851,13 @@ fi
         {conf_list_id} = {{
             isa = XCConfigurationList;
             buildConfigurations = (
-                {conf_release_id} /* Release */,
-                {conf_debug_id} /* Debug */,
+                {conf_refs}
             );
             defaultConfigurationIsVisible = 0;
             defaultConfigurationName = Release;
         }};
 
-        {conf_release_id} = {{
-            isa = XCBuildConfiguration;
-            buildSettings = {{
-                {common_build_settings}
-                "CARGO_XCODE_BUILD_MODE" = "release"; /* for xcode scripts */
-            }};
-            name = Release;
-        }};
-
-        {conf_debug_id} = {{
-            isa = XCBuildConfiguration;
-            buildSettings = {{
-                {common_build_settings}
-                "CARGO_XCODE_BUILD_MODE" = "debug"; /* for xcode scripts */
-                ONLY_ACTIVE_ARCH = YES;
-            }};
-            name = Debug;
-        }};
+        {configurations}
 
         {project_id} = {{
             isa = PBXProject;
@@ -558,13 +893,315 @@ fi
         Ok(tpl)
     }
 
-    fn prepare_project_path(&self) -> Result<PathBuf, io::Error> {
-        let proj_file_name = format!("{}.xcodeproj", self.custom_project_name.as_ref().unwrap_or(&self.package.name));
-        let proj_path = match &self.output_dir {
-            Some(path) => path.join(proj_file_name),
-            None => Path::new(&self.package.manifest_path).with_file_name(proj_file_name),
+    /// Build settings used by the build script. These are in xcconfig files, so that they can be overridden.
+    fn cargo_build_settings(&self, xcode_metadata: &XcodeMetadata) -> Result<Vec<BuildSetting>, Error> {
+        let (target_dir, shared) = match xcode_metadata.target_dir {
+            TargetDirPolicy::Project => ("$(PROJECT_TEMP_DIR)/cargo_target".into(), false),
+            // shared between all subprojects of the app
+            TargetDirPolicy::DerivedData => ("$(OBJROOT)/cargo_target".into(), true),
+            TargetDirPolicy::Workspace => {
+                let target_dir = self.workspace_target_dir.as_ref().ok_or_else(|| Error::InvalidMetadata {
+                    manifest_path: self.package.manifest_path.clone().into(),
+                    message: "target-dir = \"workspace\" needs the workspace's target dir, which the Generator hasn't been given".into(),
+                })?;
+                (format!("$(PROJECT_DIR)/{}", escape_dollars(&relative_path(target_dir, &self.project_dir())?)), true)
+            },
+        };
+        let cargo_path = match &self.options.cargo_path {
+            Some(path) => path.to_str().ok_or_else(|| Error::NonUtf8Path(path.clone()))?,
+            None => "",
         };
-        fs::create_dir_all(&proj_path)?;
-        Ok(proj_path)
+        let mut settings = vec![
+            BuildSetting::new("CARGO_TARGET_DIR", target_dir).with_comment("for cargo"),
+            BuildSetting::new("CARGO_XCODE_INPUT_FILE_LIST", format!("$(PROJECT_FILE_PATH)/{INPUT_FILE_LIST_FILE_NAME}")).with_comment("Cargo.toml, sources, build.rs"),
+            BuildSetting::new("CARGO_XCODE_CLEAN_PACKAGE", if shared { self.package.name.as_str() } else { "" }).with_comment("cleans only this package"),
+            BuildSetting::new("CARGO_XCODE_CARGO_PATH", escape_dollars(cargo_path)).with_comment("absolute path of cargo, if not in PATH"),
+            BuildSetting::new("CARGO_XCODE_EXTRA_PATH", "").with_comment("prepended to PATH, configure yourself"),
+            BuildSetting::new("CARGO_XCODE_PANIC", "").with_comment("abort or unwind, overrides Cargo profiles"),
+            BuildSetting::new("CARGO_XCODE_BUILD_ALL_ARCHS", if xcode_metadata.build_all_archs { "YES" } else { "NO" }).with_comment("YES builds all ARCHS in one cargo invocation"),
+            BuildSetting::new("CARGO_XCODE_LINKER_PLUGIN_LTO", "NO").with_comment("YES for cross-language LTO with Xcode's LLVM_LTO"),
+        ];
+        settings.extend(xcode_metadata.feature_build_settings());
+        settings.extend(xcode_metadata.cargo_args_build_settings());
+        settings.extend(platforms::target_build_settings());
+        settings.extend(xcode_metadata.deployment_target.build_settings());
+        Ok(settings)
+    }
+
+    /// Only for dylibs and executables. Xcode signs the product after the lipo phase, so it's fine that the binary is modified after Cargo.
+    fn code_sign_flags(&self, xcode_metadata: &XcodeMetadata) -> Result<String, Error> {
+        let entitlements_path = xcode_metadata.code_sign.entitlements.as_ref()
+            .map(|path| relative_path(&Path::new(&self.package.manifest_path).with_file_name(path), &self.project_dir()))
+            .transpose()?;
+        Ok(xcode_metadata.code_sign.build_settings(entitlements_path.as_deref().map(escape_dollars).as_deref()).iter()
+            .map(|s| format!("\n                    {} = \"{}\";", s.name, plist_escape(&s.value)))
+            .collect())
+    }
+
+    /// File names and contents of xcconfig files that are written inside the `.xcodeproj` dir
+    pub fn xcconfig_files(&self) -> Result<Vec<(String, String)>, Error> {
+        let xcode_metadata = self.xcode_metadata()?;
+        let local_file_name = format!("{}.local.xcconfig", self.project_name());
+        let header = format!("{XCCONFIG_HEADER} {}\n// Changes will be lost when the project is regenerated. Put your settings in {local_file_name} next to the project instead.\n", env!("CARGO_PKG_VERSION"));
+
+        let mut files = vec![(
+            SHARED_XCCONFIG_FILE_NAME.to_string(),
+            format!("{header}\n{}", xcconfig::render(&self.cargo_build_settings(&xcode_metadata)?)),
+        )];
+        files.extend(xcode_metadata.configurations().iter().map(|conf| (
+            conf.xcconfig_file_name(),
+            format!("{header}\n#include \"{SHARED_XCCONFIG_FILE_NAME}\"\n\n{}\n#include? \"../{local_file_name}\"\n", xcconfig::render(&conf.build_settings())),
+        )));
+        Ok(files)
+    }
+
+    /// Files that the build depends on, as known before the first build.
+    /// The build script adds to it files from Cargo's dep info.
+    pub fn input_file_list(&self) -> Result<String, Error> {
+        let manifest_path = Path::new(&self.package.manifest_path);
+        let mut inputs = vec![manifest_path.to_owned()];
+        let lock_path = match &self.workspace_root {
+            Some(root) => root.join("Cargo.lock"),
+            None => manifest_path.with_file_name("Cargo.lock"),
+        };
+        if lock_path.exists() {
+            inputs.push(lock_path);
+        }
+        let mut source_dirs = Vec::new();
+        // tests, examples and benches aren't built by Xcode
+        let (built_targets, other_targets): (Vec<_>, Vec<_>) = self.package.targets.iter()
+            .partition(|t| t.kind.iter().any(|k| matches!(k.as_str(), "bin" | "lib" | "staticlib" | "cdylib" | "custom-build")));
+        let mut skip_dirs = other_targets.iter().filter_map(|t| Path::new(&t.src_path).parent()).map(PathBuf::from).collect::<Vec<_>>();
+        skip_dirs.extend(self.workspace_target_dir.clone());
+        for target in built_targets {
+            let src_path = Path::new(&target.src_path);
+            inputs.push(src_path.to_owned());
+            // build.rs is usually next to Cargo.toml, and its dir has no other sources
+            if target.kind.iter().any(|k| k == "custom-build") {
+                continue;
+            }
+            if let Some(dir) = src_path.parent() {
+                if !source_dirs.iter().any(|d: &PathBuf| dir.starts_with(d)) {
+                    source_dirs.retain(|d| !d.starts_with(dir));
+                    source_dirs.push(dir.to_owned());
+                }
+            }
+        }
+        skip_dirs.retain(|skip| !source_dirs.iter().any(|dir| dir.starts_with(skip)));
+        for dir in &source_dirs {
+            collect_rust_files(dir, &skip_dirs, &mut inputs)?;
+        }
+        inputs.sort();
+        inputs.dedup();
+
+        let project_dir = self.project_dir();
+        inputs.iter().map(|path| {
+            Ok(format!("$(PROJECT_DIR)/{}\n", escape_dollars(&relative_path(path, &project_dir)?)))
+        }).collect()
+    }
+
+    /// From `Cargo.toml`, with additions from the options
+    fn xcode_metadata(&self) -> Result<XcodeMetadata, Error> {
+        let mut xcode_metadata = XcodeMetadata::from_package(&self.package)?;
+        xcode_metadata.features.extend(self.options.features.iter().cloned());
+        xcode_metadata.configurations.extend(self.options.configurations.iter().map(|(k, v)| (k.clone(), v.clone())));
+        xcode_metadata.check_configuration_names().map_err(|message| Error::InvalidMetadata { manifest_path: self.package.manifest_path.clone().into(), message })?;
+        Ok(xcode_metadata)
+    }
+
+    fn xcconfig_id(&self, conf: &Configuration) -> String {
+        self.make_id("xcconfig", &conf.xcconfig_file_name())
+    }
+
+    fn project_name(&self) -> &str {
+        self.options.project_name.as_ref().unwrap_or(&self.package.name)
+    }
+
+    /// Dir containing the `.xcodeproj`
+    /// Absolute, because paths in the project are relative to it
+    fn project_dir(&self) -> PathBuf {
+        match &self.options.output_dir {
+            Some(output_dir) => absolute_path(output_dir),
+            None => Path::new(&self.package.manifest_path).parent().unwrap().to_owned(),
+        }
+    }
+
+    fn project_file_name(&self) -> String {
+        format!("{}.xcodeproj", self.project_name())
+    }
+
+    /// The `.xcodeproj` dir
+    pub fn project_path(&self) -> Result<PathBuf, Error> {
+        check_project_name(self.project_name())?;
+        Ok(self.project_dir().join(self.project_file_name()))
+    }
+}
+
+/// Rust targets that can be installed with `rustup target add`.
+/// Other Apple platforms need nightly Rust with `rust-src` component.
+pub fn rustup_targets() -> Vec<&'static str> {
+    let mut triples = platforms::RUST_TARGETS.iter().filter(|t| t.build_std.is_none()).map(|t| t.triple).collect::<Vec<_>>();
+    triples.dedup();
+    triples
+}
+
+/// Has a kind that Xcode can build for at least one of the platforms
+fn is_supported_target(target: &Target, options: &GeneratorOptions) -> bool {
+    target.kind.iter().any(|k| (k == "bin" || k == "staticlib" || k == "cdylib") && !supported_platforms(k, options).is_empty())
+}
+
+/// Executables and dylibs are macOS-only
+fn supported_platforms(kind: &str, options: &GeneratorOptions) -> Vec<&'static str> {
+    const ALL_PLATFORMS: &[&str] = &["macosx", "iphonesimulator", "iphoneos", "appletvsimulator", "appletvos", "watchsimulator", "watchos", "xrsimulator", "xros"];
+    let platforms = if kind == "staticlib" { ALL_PLATFORMS } else { &ALL_PLATFORMS[..1] };
+    platforms.iter().copied()
+        .filter(|p| options.platforms.as_ref().is_none_or(|only| only.iter().any(|o| o == p)))
+        .collect()
+}
+
+/// Skips dot-dirs, and Cargo's target dirs, which contain build outputs, not inputs.
+/// The target dir may be unknown, but Cargo marks its target dirs with `CACHEDIR.TAG`.
+fn collect_rust_files(dir: &Path, skip_dirs: &[PathBuf], out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(Error::io(dir))? {
+        let entry = entry.map_err(Error::io(dir))?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(Error::io(&path))?;
+        if file_type.is_dir() {
+            let skip = entry.file_name().to_string_lossy().starts_with('.')
+                || skip_dirs.contains(&path)
+                || path.join("CACHEDIR.TAG").exists();
+            if !skip {
+                collect_rust_files(&path, skip_dirs, out)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Xcode reloads the project when it changes, and could see a half-written file,
+/// so files are replaced atomically, and unchanged files are left alone.
+/// Returns whether the file has been written.
+fn write_if_changed(path: &Path, contents: &str) -> Result<bool, Error> {
+    if fs::read(path).is_ok_and(|old| old == contents.as_bytes()) {
+        return Ok(false);
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{file_name}.{}.tmp", process::id()));
+    let res = fs::File::create(&tmp_path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()).and_then(|()| f.sync_all()))
+        .and_then(|()| fs::rename(&tmp_path, path));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(Error::Io { path: path.into(), error: e });
+    }
+    Ok(true)
+}
+
+/// The name is used for the `.xcodeproj` file and products
+fn check_project_name(name: &str) -> Result<(), Error> {
+    let reason = if name.is_empty() {
+        "it's empty"
+    } else if name.starts_with('.') {
+        "it starts with a dot"
+    } else if name.contains(['/', '\0']) {
+        "it contains a path separator"
+    } else {
+        return Ok(());
+    };
+    Err(Error::InvalidTargetName { name: name.into(), reason })
+}
+
+/// UTF-8 path for project files
+/// Relative to the current dir, with `..` resolved, because `diff_paths` can't handle `..` in the base.
+/// The dir may not exist yet, so it can't be canonicalized.
+fn absolute_path(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::ParentDir => { out.pop(); },
+            Component::CurDir => {},
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `diff_paths` returns an absolute `path` unchanged when `base` is relative, which would be wrong after `$(PROJECT_DIR)/`
+fn relative_path(path: &Path, base: &Path) -> Result<String, Error> {
+    let relative = pathdiff::diff_paths(path, base).filter(|p| p.is_relative())
+        .ok_or_else(|| Error::NoRelativePath { path: path.into(), base: base.into() })?;
+    relative.into_os_string().into_string().map_err(|path| Error::NonUtf8Path(path.into()))
+}
+
+fn configuration_refs(conf_ids: &[(String, &Configuration)]) -> String {
+    conf_ids.iter().map(|(id, conf)| format!("{id} /* {} */,\n", plist_comment(&conf.name))).collect()
+}
+
+/// Escapes contents of a `"…"` string in the OpenStep plist format of `project.pbxproj`.
+/// The file is UTF-8, so non-ASCII characters don't need escaping.
+pub(crate) fn plist_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Text for `/* … */` comments, which can't be escaped
+fn plist_comment(s: &str) -> String {
+    s.replace("*/", "* /")
+}
+
+/// Xcode expands `$(…)` and `$VAR` in build settings and xcfilelists, so literal `$` must be escaped
+pub(crate) fn escape_dollars(s: &str) -> String {
+    s.replace('$', "$(DOLLAR)")
+}
+
+/// Quotes for `sh`, unless it's made only of safe characters
+pub(crate) fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.bytes().all(|c| c.is_ascii_alphanumeric() || b"-_=+,./:@%".contains(&c)) {
+        s.to_owned()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plist_strings() {
+        assert_eq!(plist_escape(r#"a "b" \c"#), r#"a \"b\" \\c"#);
+        assert_eq!(plist_escape("line\n\tnext 'é' $x"), "line\\n\\tnext 'é' $x");
+    }
+
+    #[test]
+    fn plist_comments() {
+        assert_eq!(plist_comment("a */ b /* c"), "a * / b /* c");
+    }
+
+    #[test]
+    fn dollars() {
+        assert_eq!(escape_dollars("$(HOME) $x"), "$(DOLLAR)(HOME) $(DOLLAR)x");
+        assert_eq!(escape_dollars("no dollars"), "no dollars");
+    }
+
+    #[test]
+    fn shell_words() {
+        assert_eq!(shell_quote("--bin=my-tool_1.0"), "--bin=my-tool_1.0");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("my tool$x"), "'my tool$x'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("é"), "'é'");
     }
 }
@@ -1,74 +1,440 @@
-use cargo_metadata::{Package, Target};
-use getopts::Options;
+use cargo_metadata::Metadata;
+use cargo_xcode::{Error, Generator, GeneratorOptions, ProjectReport, TargetReport};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use notify::{RecursiveMode, Watcher};
+use std::collections::BTreeSet;
 use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{exit, Command};
+use std::sync::mpsc;
+use std::time::Duration;
 
-use std::process::exit;
+/// cargo-xcode generates Xcode project files for Cargo crates
+#[derive(Parser)]
+#[command(name = "cargo-xcode", bin_name = "cargo xcode", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+    #[command(flatten)]
+    generate: GenerateArgs,
+    #[command(flatten)]
+    project: ProjectArgs,
+    #[command(flatten)]
+    output: Output,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Write Xcode projects (default)
+    Generate(GenerateArgs),
+    /// Fail with exit status 6 if any project is missing or out of date
+    Check,
+    /// Delete generated Xcode projects
+    Clean,
+    /// Install rustup targets needed to build for Apple platforms
+    Setup {
+        /// Also install nightly Rust with rust-src, needed for tvOS, watchOS, visionOS and Mac Catalyst
+        #[arg(long)]
+        nightly: bool,
+    },
+    /// List projects and targets that would be generated
+    Info,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// Regenerate projects whenever Cargo.toml files change
+    #[arg(long)]
+    watch: bool,
+}
+
+#[derive(Args)]
+struct ProjectArgs {
+    /// Location of the Rust/Cargo project to convert
+    #[arg(long, global = true, value_name = "Cargo.toml")]
+    manifest_path: Option<String>,
+    /// Where to write xcodeproj to (default: same directory as the crate)
+    #[arg(long, global = true, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Override crate name to use a different name in Xcode
+    #[arg(long, global = true, value_name = "NAME")]
+    project_name: Option<String>,
+    /// Only this workspace member (can be repeated)
+    #[arg(short, long = "package", global = true, value_name = "NAME")]
+    packages: Vec<String>,
+    /// All workspace members (default)
+    #[arg(long, global = true, conflicts_with = "packages")]
+    workspace: bool,
+    /// Skip this workspace member (can be repeated)
+    #[arg(long, global = true, value_name = "NAME")]
+    exclude: Vec<String>,
+    /// Only the library targets, and targets selected with --bin/--bins
+    #[arg(long, global = true)]
+    lib: bool,
+    /// Only this binary target, and targets selected with --lib/--bins (can be repeated)
+    #[arg(long = "bin", global = true, value_name = "NAME")]
+    bins: Vec<String>,
+    /// Only binary targets, and the library if --lib is used
+    #[arg(long = "bins", global = true)]
+    all_bins: bool,
+    /// Save path of the cargo running `cargo xcode` in CARGO_XCODE_CARGO_PATH, for toolchains not managed by rustup (e.g. Nix)
+    #[arg(long, global = true)]
+    save_cargo_path: bool,
+}
+
+#[derive(Args)]
+struct Output {
+    /// No output other than errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also list targets of each project
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// `json` prints one JSON object per project on stdout
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
 
 fn main() {
-    let mut opts = Options::new();
-    opts.optopt("", "manifest-path", "Location of the Rust/Cargo project to convert.", "Cargo.toml");
-    opts.optopt("", "output-dir", "Where to write xcodeproj to (default: same directory as the crate)", "");
-    opts.optopt("", "project-name", "Override crate name to use a differnet name in Xcode", "");
-    opts.optflag("h", "help", "This help.");
-    let matches = match opts.parse(env::args().skip(1)) {
-        Ok(m) => m,
-        Err(f) => {
-            eprintln!("error: {}", f);
-            exit(1);
-        },
+    // `cargo xcode` runs `cargo-xcode xcode`
+    let args = env::args_os().enumerate().filter(|(i, arg)| *i != 1 || arg != "xcode").map(|(_, arg)| arg);
+    let cli = Cli::try_parse_from(args).unwrap_or_else(|e| {
+        let _ = e.print();
+        // clap's 2 would look like a `cargo metadata` failure
+        exit(if e.use_stderr() { 1 } else { 0 });
+    });
+
+    let out = &cli.output;
+    let project = &cli.project;
+    let options = project.options();
+    let manifest_path = project.manifest_path.as_deref();
+    if cli.generate.watch && !matches!(cli.command, None | Some(Cmd::Generate(_))) {
+        eprintln!("error: --watch can only be used with the generate subcommand");
+        exit(1);
+    }
+    match cli.command {
+        None => generate_cmd(manifest_path, project, &options, &cli.generate, out),
+        Some(Cmd::Generate(args)) => generate_cmd(manifest_path, project, &options, &GenerateArgs { watch: args.watch || cli.generate.watch }, out),
+        Some(Cmd::Check) => check(manifest_path, project, &options, out),
+        Some(Cmd::Clean) => clean(manifest_path, project, &options, out),
+        Some(Cmd::Setup { nightly }) => setup(nightly, out),
+        Some(Cmd::Info) => info(manifest_path, project, &options, out),
+    }
+}
+
+fn generate_cmd(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, args: &GenerateArgs, out: &Output) {
+    let (meta, generated) = match generate(manifest_path, project, options, out) {
+        Ok(res) => res,
+        Err(e) => fail(&e),
     };
+    if args.watch {
+        watch(manifest_path, project, options, out, meta);
+    }
+    if generated == 0 {
+        exit(1);
+    }
+}
 
-    if matches.opt_present("help") {
-        println!("{}", opts.usage("cargo-xcode generates Xcode project files for Cargo crates"));
-        exit(0);
+/// Returns metadata used, so that its manifests can be watched, and number of projects
+fn generate(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output) -> Result<(Metadata, usize), Error> {
+    let (meta, generators) = generators(manifest_path, project, options, out)?;
+    for g in &generators {
+        let report = g.write_project()?;
+        out.project("generated", &report, None, || format!("OK:\n{}", report.path.display()));
     }
+    Ok((meta, generators.len()))
+}
 
-    for arg in matches.free.iter().filter(|&arg| arg != "xcode") {
-        eprintln!("warning: '{}' arg unused", arg);
+/// Compares projects generated in memory with the files on disk
+fn check(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output) {
+    let res = generators(manifest_path, project, options, out).and_then(|(_, generators)| {
+        let mut stale = 0;
+        for g in &generators {
+            let report = g.report()?;
+            let up_to_date = g.project_files()?.iter().all(|(path, content)| fs::read(path).is_ok_and(|c| c == content.as_bytes()))
+                && g.stale_files()?.is_empty();
+            if !up_to_date {
+                stale += 1;
+            }
+            let status = match (up_to_date, report.path.exists()) {
+                (true, _) => "up to date",
+                (false, true) => "out of date, run `cargo xcode` to regenerate",
+                (false, false) => "missing, run `cargo xcode` to generate",
+            };
+            out.project("checked", &report, Some(("up_to_date", up_to_date)), || format!("{}: {status}", report.path.display()));
+        }
+        Ok((generators.len(), stale))
+    });
+    match res {
+        Ok((0, _)) => exit(1),
+        Ok((_, 0)) => {},
+        Ok(_) => exit(6),
+        Err(e) => fail(&e),
     }
+}
+
+/// Deletes only dirs that look like they've been generated, so that hand-made projects with the same name survive
+fn clean(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output) {
+    let res = generators(manifest_path, project, options, out).and_then(|(_, generators)| {
+        for g in &generators {
+            let report = g.report()?;
+            let pbxproj = report.path.join("project.pbxproj");
+            let removed = match fs::read_to_string(&pbxproj) {
+                Ok(content) if content.contains("generated with cargo-xcode") => {
+                    fs::remove_dir_all(&report.path).map_err(|error| Error::Io { path: report.path.clone(), error })?;
+                    true
+                },
+                Ok(_) => {
+                    out.warn(format_args!("{} wasn't generated by cargo-xcode, not removing", report.path.display()));
+                    false
+                },
+                Err(_) => false,
+            };
+            out.project("cleaned", &report, Some(("removed", removed)), || {
+                format!("{}: {}", report.path.display(), if removed { "removed" } else { "not removed" })
+            });
+        }
+        Ok(())
+    });
+    if let Err(e) = res {
+        fail(&e);
+    }
+}
+
+fn setup(nightly: bool, out: &Output) {
+    let mut commands = vec![["target", "add"].into_iter().chain(cargo_xcode::rustup_targets()).collect::<Vec<_>>()];
+    if nightly {
+        commands.push(vec!["toolchain", "install", "nightly", "--component", "rust-src"]);
+    }
+    for args in commands {
+        if !out.quiet {
+            eprintln!("Running rustup {}", args.join(" "));
+        }
+        match Command::new("rustup").args(&args).status() {
+            Ok(status) if status.success() => {},
+            Ok(status) => {
+                eprintln!("error: rustup failed ({status})");
+                exit(1);
+            },
+            Err(e) => {
+                eprintln!("error: can't run rustup. Install it from https://rustup.rs\n  caused by: {e}");
+                exit(1);
+            },
+        }
+    }
+}
 
-    let path = matches.opt_str("manifest-path");
-    let output_dir = matches.opt_str("output-dir");
+fn info(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output) {
+    let res = generators(manifest_path, project, options, out).and_then(|(_, generators)| {
+        for g in &generators {
+            let report = g.report()?;
+            out.project("info", &report, None, || {
+                let mut s = format!("{} ({})", report.path.display(), report.package);
+                for t in &report.targets {
+                    s += &format!("\n  {}", target_summary(t));
+                }
+                s
+            });
+        }
+        Ok(generators.len())
+    });
+    match res {
+        Ok(0) => exit(1),
+        Ok(_) => {},
+        Err(e) => fail(&e),
+    }
+}
+
+fn generators(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output) -> Result<(Metadata, Vec<Generator>), Error> {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.no_deps();
-    if let Some(ref path) = path {
+    if let Some(path) = manifest_path {
         cmd.manifest_path(path);
     }
-    let meta = match cmd.exec() {
-        Ok(m) => m,
-        Err(e) => {
-            eprintln!("error: Can't parse cargo metadata in {:?} because: {}", path, e);
-            exit(1);
-        },
-    };
+    let meta = cmd.exec()?;
+    project.check(&meta)?;
+    let generators = Generator::from_metadata(&meta, options.clone());
+    if generators.is_empty() {
+        out.warn(format_args!(r#"No libraries with crate-type "staticlib" or "cdylib""#));
+    }
+    Ok((meta, generators))
+}
 
-    let custom_project_name = matches.opt_str("project-name");
+/// Regenerates after any workspace member's or the workspace's `Cargo.toml` changes.
+/// Unchanged project files aren't rewritten, so Xcode reloads only projects that have changed.
+fn watch(manifest_path: Option<&str>, project: &ProjectArgs, options: &GeneratorOptions, out: &Output, mut meta: Metadata) -> ! {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).unwrap_or_else(|e| fail_watch(&e));
+    let mut watched_dirs = BTreeSet::new();
+    loop {
+        let manifests = watched_manifests(&meta);
+        // editors replace files instead of modifying them, so dirs are watched, not the files
+        let dirs = manifests.iter().filter_map(|m| m.parent()).map(PathBuf::from).collect::<BTreeSet<_>>();
+        for dir in watched_dirs.difference(&dirs) {
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&watched_dirs) {
+            watcher.watch(dir, RecursiveMode::NonRecursive).unwrap_or_else(|e| fail_watch(&e));
+        }
+        watched_dirs = dirs;
+        if !out.quiet {
+            eprintln!("Watching {} Cargo.toml file(s) for changes", manifests.len());
+        }
 
-    let ok = meta.packages
-        .into_iter()
-        .filter_map(filter_package)
-        .map(move |p| {
-            let g = cargo_xcode::Generator::new(p, output_dir.as_ref().map(From::from), custom_project_name.clone());
-            let p = g.write_pbxproj().unwrap();
-            println!("OK:\n{}", p.display());
-        })
-        .count();
+        loop {
+            let event = match rx.recv() {
+                Ok(event) => event.unwrap_or_else(|e| fail_watch(&e)),
+                Err(_) => exit(0),
+            };
+            if event.kind.is_access() || !event.paths.iter().any(|p| manifests.contains(p)) {
+                continue;
+            }
+            // saving often causes a burst of events
+            while rx.recv_timeout(Duration::from_millis(200)).is_ok() {}
+            break;
+        }
 
-    if ok == 0 {
-        eprintln!(r#"warning: No libraries with crate-type "staticlib" or "cdylib""#);
-        exit(1);
+        match generate(manifest_path, project, options, out) {
+            Ok((new_meta, _)) => meta = new_meta,
+            // keep watching, so that the mistake can be fixed
+            Err(e) => print_error(&e),
+        }
     }
 }
 
-fn filter_package(mut package: Package) -> Option<Package> {
-    package.targets.retain(is_relevant_target);
-    if package.targets.is_empty() {
-        None
-    } else {
-        Some(package)
+impl Output {
+    /// JSON goes to stdout regardless of `--quiet`, because scripts asked for it
+    fn project(&self, reason: &str, report: &ProjectReport, field: Option<(&str, bool)>, human: impl FnOnce() -> String) {
+        if self.message_format == MessageFormat::Json {
+            let mut json = serde_json::json!({ "reason": reason });
+            if let (serde_json::Value::Object(obj), Ok(serde_json::Value::Object(report))) = (&mut json, serde_json::to_value(report)) {
+                obj.extend(report);
+                if let Some((name, value)) = field {
+                    obj.insert(name.into(), value.into());
+                }
+            }
+            println!("{json}");
+        } else if !self.quiet {
+            println!("{}", human());
+            if self.verbose && reason != "info" {
+                for t in &report.targets {
+                    println!("  {}", target_summary(t));
+                }
+            }
+        }
+    }
+
+    fn warn(&self, msg: std::fmt::Arguments<'_>) {
+        if !self.quiet {
+            eprintln!("warning: {msg}");
+        }
     }
 }
 
-fn is_relevant_target(target: &Target) -> bool {
-    target.kind.iter().any(|k| k == "bin" || k == "staticlib" || k == "cdylib")
+impl ProjectArgs {
+    fn options(&self) -> GeneratorOptions {
+        let mut options = GeneratorOptions::new();
+        if !self.packages.is_empty() || !self.exclude.is_empty() {
+            let (packages, exclude) = (self.packages.clone(), self.exclude.clone());
+            options = options.package_filter(move |p| {
+                (packages.is_empty() || packages.contains(&p.name)) && !exclude.contains(&p.name)
+            });
+        }
+        if self.lib || self.all_bins || !self.bins.is_empty() {
+            let (lib, all_bins, bins) = (self.lib, self.all_bins, self.bins.clone());
+            options = options.target_filter(move |t| t.kind.iter().any(|kind| match kind.as_str() {
+                "bin" => all_bins || bins.contains(&t.name),
+                "lib" | "staticlib" | "cdylib" => lib,
+                _ => false,
+            }));
+        }
+        if let Some(output_dir) = &self.output_dir {
+            options = options.output_dir(output_dir);
+        }
+        if let Some(project_name) = &self.project_name {
+            options = options.project_name(project_name);
+        }
+        if self.save_cargo_path {
+            match detect_cargo_path() {
+                Some(path) => options = options.cargo_path(path),
+                None => eprintln!("warning: --save-cargo-path needs cargo that isn't managed by rustup. Run this as `cargo xcode`, or set CARGO_XCODE_CARGO_PATH yourself"),
+            }
+        }
+        options
+    }
+
+    /// Like Cargo, names that don't match anything are errors
+    fn check(&self, meta: &Metadata) -> Result<(), Error> {
+        let members = meta.workspace_packages();
+        if let Some(name) = self.packages.iter().find(|&name| !members.iter().any(|p| &p.name == name)) {
+            return Err(Error::PackageNotFound { name: name.clone() });
+        }
+        let selected = members.iter().filter(|p| self.packages.is_empty() || self.packages.contains(&p.name));
+        let bin_names = selected.flat_map(|p| &p.targets).filter(|t| t.kind.iter().any(|k| k == "bin")).map(|t| &t.name).collect::<Vec<_>>();
+        if let Some(name) = self.bins.iter().find(|&name| !bin_names.contains(&name)) {
+            return Err(Error::BinNotFound { name: name.clone() });
+        }
+        Ok(())
+    }
+}
+
+/// Absolute path of the cargo executable that has been used to run `cargo xcode`.
+///
+/// Rustup's toolchain-specific binaries are skipped, because the rustup proxy in `PATH`
+/// is needed to switch toolchains (e.g. `+nightly`).
+fn detect_cargo_path() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var_os("CARGO")?);
+    if !path.is_absolute() || path.components().any(|c| c.as_os_str() == ".rustup") {
+        return None;
+    }
+    Some(path)
+}
+
+fn target_summary(t: &TargetReport) -> String {
+    format!("{}: {} from {} ({}, {})", t.name, t.file_name, t.cargo_file_name, t.kind, t.supported_platforms.join(" "))
+}
+
+fn watched_manifests(meta: &Metadata) -> BTreeSet<PathBuf> {
+    meta.workspace_packages().into_iter()
+        .map(|p| p.manifest_path.clone().into_std_path_buf())
+        .chain([meta.workspace_root.join("Cargo.toml").into_std_path_buf()])
+        .collect()
+}
+
+fn fail_watch(error: &notify::Error) -> ! {
+    eprintln!("error: can't watch Cargo.toml files for changes\n  caused by: {error}");
+    exit(5);
+}
+
+fn fail(error: &Error) -> ! {
+    print_error(error);
+    exit(exit_code(error));
+}
+
+fn print_error(error: &Error) {
+    eprintln!("error: {error}");
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        eprintln!("  caused by: {e}");
+        source = e.source();
+    }
+}
+
+/// Distinct for each kind of failure, so that scripts can tell them apart. 1 is also for invalid args or no targets.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::PackageNotFound { .. } | Error::BinNotFound { .. } => 1,
+        Error::CargoMetadata(_) => 2,
+        Error::InvalidMetadata { .. } | Error::InvalidTargetName { .. } => 3,
+        Error::NonUtf8Path(_) | Error::NoRelativePath { .. } => 4,
+        Error::Io { .. } => 5,
+        _ => 1,
+    }
 }
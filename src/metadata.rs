@@ -0,0 +1,299 @@
+//! Settings from `[package.metadata.xcode]` in `Cargo.toml`
+
+use crate::{escape_dollars, shell_quote, Error};
+use crate::xcconfig::BuildSetting;
+use cargo_metadata::Package;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct XcodeMetadata {
+    pub deployment_target: DeploymentTargets,
+    /// Xcode configuration name => Cargo profile name
+    pub configurations: BTreeMap<String, String>,
+    /// Cargo features enabled in all configurations
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub all_features: bool,
+    /// Xcode configuration name => additional Cargo features
+    pub configuration_features: BTreeMap<String, Vec<String>>,
+    /// Cargo target name => additional Cargo features
+    pub target_features: BTreeMap<String, Vec<String>>,
+    /// Passed to `cargo build`, e.g. `--locked`
+    pub extra_args: Vec<String>,
+    /// Env vars for cargo, e.g. `RUSTFLAGS`
+    pub env: BTreeMap<String, String>,
+    /// Xcode configuration name => additional args for `cargo build`
+    pub configuration_extra_args: BTreeMap<String, Vec<String>>,
+    /// Xcode configuration name => additional env vars
+    pub configuration_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// Static libs in release configurations contain LLVM bitcode for Xcode's LTO
+    pub cross_language_lto: bool,
+    /// Signing of dylibs and executables
+    pub code_sign: CodeSign,
+    /// Make dylibs ready to be embedded in app bundles
+    pub embed_dylib: bool,
+    /// Universal builds run one cargo build with a `--target` for every arch
+    pub build_all_archs: bool,
+    pub target_dir: TargetDirPolicy,
+}
+
+/// Where Cargo's target dir is
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum TargetDirPolicy {
+    /// Separate for each Xcode project
+    #[default]
+    Project,
+    /// Workspace's `target/`, shared with command-line builds
+    Workspace,
+    /// Shared by all projects built together in Xcode's DerivedData
+    DerivedData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct CodeSign {
+    /// e.g. `Developer ID Application`, or `-` for ad-hoc signing
+    pub identity: Option<String>,
+    pub team: Option<String>,
+    /// `Automatic` or `Manual`
+    pub style: Option<String>,
+    pub hardened_runtime: bool,
+    /// Path relative to `Cargo.toml`
+    pub entitlements: Option<String>,
+}
+
+/// Xcode build configuration, and Cargo profile used for it
+pub(crate) struct Configuration {
+    pub name: String,
+    pub profile: String,
+    pub features: Vec<String>,
+    pub extra_args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cross_language_lto: bool,
+}
+
+/// Minimum OS versions, e.g. `macos = "10.15"`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct DeploymentTargets {
+    pub macos: Option<String>,
+    pub ios: Option<String>,
+    pub tvos: Option<String>,
+    pub watchos: Option<String>,
+    pub visionos: Option<String>,
+}
+
+impl XcodeMetadata {
+    pub fn from_package(package: &Package) -> Result<Self, Error> {
+        let invalid = |message: String| Error::InvalidMetadata { manifest_path: package.manifest_path.clone().into(), message };
+        let meta: Self = match package.metadata.get("xcode") {
+            None => return Ok(Self::default()),
+            Some(xcode) => serde_json::from_value(xcode.clone()).map_err(|e| invalid(e.to_string()))?,
+        };
+
+        meta.check_configuration_names().map_err(invalid)?;
+        let configurations = meta.configurations();
+        let is_configuration = |name: &String| configurations.iter().any(|c| &c.name == name);
+        for (key, names) in [
+            ("configuration-features", meta.configuration_features.keys().collect::<Vec<_>>()),
+            ("configuration-extra-args", meta.configuration_extra_args.keys().collect()),
+            ("configuration-env", meta.configuration_env.keys().collect()),
+        ] {
+            if let Some(name) = names.into_iter().find(|&name| !is_configuration(name)) {
+                return Err(invalid(format!("{key} has '{name}', but there's no such configuration")));
+            }
+        }
+        let is_env_name = |name: &str| !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_');
+        if let Some(name) = meta.env.keys().chain(meta.configuration_env.values().flat_map(|e| e.keys())).find(|name| !is_env_name(name)) {
+            return Err(invalid(format!("'{name}' is not a valid env var name")));
+        }
+        let is_version = |v: &str| !v.is_empty() && v.split('.').all(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()));
+        if let Some(version) = meta.deployment_target.versions().into_iter().find(|v| !is_version(v)) {
+            return Err(invalid(format!("deployment-target '{version}' is not a version number like \"10.15\"")));
+        }
+        if let Some(style) = meta.code_sign.style.as_ref().filter(|&style| style != "Automatic" && style != "Manual") {
+            return Err(invalid(format!("code-sign style '{style}' must be \"Automatic\" or \"Manual\"")));
+        }
+        if let Some(name) = meta.target_features.keys().find(|&name| !package.targets.iter().any(|t| &t.name == name)) {
+            return Err(invalid(format!("target-features has '{name}', but there's no such target")));
+        }
+        Ok(meta)
+    }
+
+    /// Names are used for `.xcconfig` file names, and lowercased for object IDs
+    pub fn check_configuration_names(&self) -> Result<(), String> {
+        let configurations = self.configurations();
+        for (i, c) in configurations.iter().enumerate() {
+            let name = &c.name;
+            if name.is_empty() || name.starts_with('.') || name.contains(['/', '\0']) {
+                return Err(format!("configuration name '{name}' can't be used as a file name"));
+            }
+            if name.eq_ignore_ascii_case("CargoXcode") {
+                return Err(format!("configuration name '{name}' is reserved for the shared xcconfig file"));
+            }
+            if let Some(other) = configurations[..i].iter().find(|other| other.name.to_lowercase() == name.to_lowercase()) {
+                return Err(format!("configurations '{}' and '{name}' differ only in case", other.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Debug and Release are always there, because Xcode uses them as fallbacks
+    pub fn configurations(&self) -> Vec<Configuration> {
+        let mut configurations = vec![
+            Configuration::new("Release", "release"),
+            Configuration::new("Debug", "dev"),
+        ];
+        for (name, profile) in &self.configurations {
+            match configurations.iter_mut().find(|c| &c.name == name) {
+                Some(c) => c.profile.clone_from(profile),
+                None => configurations.push(Configuration::new(name, profile)),
+            }
+        }
+        for c in &mut configurations {
+            if let Some(features) = self.configuration_features.get(&c.name) {
+                c.features.clone_from(features);
+            }
+            if let Some(extra_args) = self.configuration_extra_args.get(&c.name) {
+                c.extra_args.clone_from(extra_args);
+            }
+            if let Some(env) = self.configuration_env.get(&c.name) {
+                c.env.clone_from(env);
+            }
+            c.cross_language_lto = self.cross_language_lto && !c.is_debug();
+        }
+        configurations
+    }
+
+    pub fn feature_build_settings(&self) -> Vec<BuildSetting> {
+        vec![
+            BuildSetting::new("CARGO_XCODE_FEATURES", self.features.join(" ")).with_comment("space-separated"),
+            BuildSetting::new("CARGO_XCODE_NO_DEFAULT_FEATURES", yes_no(self.no_default_features)),
+            BuildSetting::new("CARGO_XCODE_ALL_FEATURES", yes_no(self.all_features)),
+        ]
+    }
+
+    pub fn cargo_args_build_settings(&self) -> Vec<BuildSetting> {
+        vec![
+            BuildSetting::new("CARGO_XCODE_EXTRA_ARGS", shell_words(&self.extra_args)).with_comment("shell-quoted args for cargo build"),
+            BuildSetting::new("CARGO_XCODE_ENV", shell_env(&self.env)).with_comment("shell-quoted NAME=value pairs for cargo"),
+        ]
+    }
+}
+
+impl Configuration {
+    fn new(name: &str, profile: &str) -> Self {
+        Self {
+            name: name.into(),
+            profile: profile.into(),
+            features: Vec::new(),
+            extra_args: Vec::new(),
+            env: BTreeMap::new(),
+            cross_language_lto: false,
+        }
+    }
+
+    /// Name of the directory in Cargo's target dir
+    pub fn profile_dir_name(&self) -> &str {
+        match self.profile.as_str() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            other => other,
+        }
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.profile_dir_name() == "debug"
+    }
+
+    pub fn build_settings(&self) -> Vec<BuildSetting> {
+        let mut settings = vec![
+            BuildSetting::new("CARGO_XCODE_BUILD_PROFILE", &self.profile).with_comment("for cargo --profile"),
+            BuildSetting::new("CARGO_XCODE_BUILD_MODE", self.profile_dir_name()).with_comment("for xcode scripts"),
+        ];
+        if self.is_debug() {
+            settings.push(BuildSetting::new("ONLY_ACTIVE_ARCH", "YES"));
+            settings.push(BuildSetting::new("DEBUG_INFORMATION_FORMAT", "dwarf"));
+        } else {
+            settings.push(BuildSetting::new("DEBUG_INFORMATION_FORMAT", "dwarf-with-dsym"));
+            settings.push(BuildSetting::new("CARGO_XCODE_DEBUG_INFO", "").with_comment("e.g. line-tables-only, overrides debug of the Cargo profile"));
+        }
+        if self.cross_language_lto {
+            settings.push(BuildSetting::new("CARGO_XCODE_LINKER_PLUGIN_LTO", "YES").with_comment("static libs contain LLVM bitcode"));
+            settings.push(BuildSetting::new("LLVM_LTO", "YES"));
+        }
+        if !self.features.is_empty() {
+            settings.push(BuildSetting::new("CARGO_XCODE_FEATURES", format!("$(inherited) {}", self.features.join(" "))));
+        }
+        if !self.extra_args.is_empty() {
+            settings.push(BuildSetting::new("CARGO_XCODE_EXTRA_ARGS", format!("$(inherited) {}", shell_words(&self.extra_args))));
+        }
+        if !self.env.is_empty() {
+            settings.push(BuildSetting::new("CARGO_XCODE_ENV", format!("$(inherited) {}", shell_env(&self.env))));
+        }
+        settings
+    }
+
+    pub fn xcconfig_file_name(&self) -> String {
+        format!("{}.xcconfig", self.name)
+    }
+}
+
+impl CodeSign {
+    /// `entitlements_path` must be relative to the Xcode project's dir
+    pub fn build_settings(&self, entitlements_path: Option<&str>) -> Vec<BuildSetting> {
+        let mut settings = Vec::new();
+        if let Some(identity) = &self.identity {
+            settings.push(BuildSetting::new("CODE_SIGN_IDENTITY", escape_dollars(identity)));
+        }
+        if let Some(team) = &self.team {
+            settings.push(BuildSetting::new("DEVELOPMENT_TEAM", escape_dollars(team)));
+        }
+        if let Some(style) = &self.style {
+            settings.push(BuildSetting::new("CODE_SIGN_STYLE", style));
+        }
+        if self.hardened_runtime {
+            settings.push(BuildSetting::new("ENABLE_HARDENED_RUNTIME", "YES").with_comment("required for notarization"));
+        }
+        if let Some(path) = entitlements_path {
+            settings.push(BuildSetting::new("CODE_SIGN_ENTITLEMENTS", path));
+        }
+        settings
+    }
+}
+
+impl DeploymentTargets {
+    fn versions(&self) -> Vec<&str> {
+        [&self.macos, &self.ios, &self.tvos, &self.watchos, &self.visionos].into_iter().flatten().map(String::as_str).collect()
+    }
+
+    /// Xcode's build settings, which are also env vars read by rustc
+    pub fn build_settings(&self) -> Vec<BuildSetting> {
+        [
+            ("MACOSX_DEPLOYMENT_TARGET", &self.macos),
+            ("IPHONEOS_DEPLOYMENT_TARGET", &self.ios),
+            ("TVOS_DEPLOYMENT_TARGET", &self.tvos),
+            ("WATCHOS_DEPLOYMENT_TARGET", &self.watchos),
+            ("XROS_DEPLOYMENT_TARGET", &self.visionos),
+        ]
+        .into_iter()
+        .filter_map(|(name, version)| Some(BuildSetting::new(name, version.as_ref()?)))
+        .collect()
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "YES" } else { "NO" }
+}
+
+/// Quoted for the shell, and escaped for Xcode, which would otherwise expand `$` before the shell sees it
+fn shell_words(args: &[String]) -> String {
+    escape_dollars(&args.iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" "))
+}
+
+fn shell_env(env: &BTreeMap<String, String>) -> String {
+    escape_dollars(&env.iter().map(|(k, v)| format!("{k}={}", shell_quote(v))).collect::<Vec<_>>().join(" "))
+}
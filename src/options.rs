@@ -0,0 +1,134 @@
+//! Settings for embedding cargo-xcode in other tools
+
+use cargo_metadata::{Package, Target};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+type PackageFilter = dyn Fn(&Package) -> bool + Send + Sync;
+type TargetFilter = dyn Fn(&Target) -> bool + Send + Sync;
+
+/// Options of the [`Generator`](crate::Generator), in addition to `[package.metadata.xcode]` in `Cargo.toml`
+///
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let metadata = cargo_metadata::MetadataCommand::new().no_deps().exec()?;
+/// let options = cargo_xcode::GeneratorOptions::new()
+///     .output_dir("build/xcode")
+///     .features(["ffi"]);
+/// for generator in cargo_xcode::Generator::from_metadata(&metadata, options) {
+///     generator.write_pbxproj()?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Default)]
+pub struct GeneratorOptions {
+    pub(crate) output_dir: Option<PathBuf>,
+    pub(crate) project_name: Option<String>,
+    pub(crate) platforms: Option<Vec<String>>,
+    pub(crate) configurations: BTreeMap<String, String>,
+    pub(crate) features: Vec<String>,
+    pub(crate) package_filter: Option<Arc<PackageFilter>>,
+    pub(crate) target_filter: Option<Arc<TargetFilter>>,
+    pub(crate) id_seed: Option<String>,
+    pub(crate) cargo_path: Option<PathBuf>,
+}
+
+impl GeneratorOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where to write the `.xcodeproj` (default: same directory as the crate's `Cargo.toml`)
+    #[must_use]
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Name of the `.xcodeproj` and its products, instead of the crate's name
+    #[must_use]
+    pub fn project_name(mut self, name: impl Into<String>) -> Self {
+        self.project_name = Some(name.into());
+        self
+    }
+
+    /// Xcode's `SUPPORTED_PLATFORMS` names, e.g. `macosx`, `iphoneos`, `iphonesimulator`.
+    /// Limits platforms of static libraries, which otherwise support all of them. Executables and dylibs are always macOS-only, so they're skipped if `macosx` isn't included.
+    #[must_use]
+    pub fn platforms<S: Into<String>>(mut self, platforms: impl IntoIterator<Item = S>) -> Self {
+        self.platforms = Some(platforms.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Adds an Xcode configuration using the Cargo profile, or changes the profile of an existing one.
+    /// The name is used as a file name, so generation fails with [`Error::InvalidMetadata`](crate::Error::InvalidMetadata) if it's not a valid one.
+    #[must_use]
+    pub fn configuration(mut self, name: impl Into<String>, cargo_profile: impl Into<String>) -> Self {
+        self.configurations.insert(name.into(), cargo_profile.into());
+        self
+    }
+
+    /// Cargo features enabled in all configurations, in addition to the ones from `Cargo.toml`
+    #[must_use]
+    pub fn features<S: Into<String>>(mut self, features: impl IntoIterator<Item = S>) -> Self {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Which workspace members get Xcode projects (default: all that have supported targets)
+    #[must_use]
+    pub fn package_filter(mut self, filter: impl Fn(&Package) -> bool + Send + Sync + 'static) -> Self {
+        self.package_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Which Cargo targets get Xcode targets. Only `bin`, `staticlib` and `cdylib` are supported, and used by default.
+    #[must_use]
+    pub fn target_filter(mut self, filter: impl Fn(&Target) -> bool + Send + Sync + 'static) -> Self {
+        self.target_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Object IDs in the project file are derived from this, instead of Cargo's package ID.
+    /// Cargo's package ID contains the crate's absolute path, so a fixed seed makes IDs the same on all machines.
+    #[must_use]
+    pub fn id_seed(mut self, seed: impl Into<String>) -> Self {
+        self.id_seed = Some(seed.into());
+        self
+    }
+
+    /// Saved in the `CARGO_XCODE_CARGO_PATH` build setting, for toolchains that aren't in Xcode's `PATH` (e.g. Nix).
+    /// It's machine-specific, so projects shared with others are better off with it set in the `.local.xcconfig` instead.
+    #[must_use]
+    pub fn cargo_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cargo_path = Some(path.into());
+        self
+    }
+
+    pub(crate) fn is_package_included(&self, package: &Package) -> bool {
+        self.package_filter.as_ref().is_none_or(|filter| filter(package))
+    }
+
+    pub(crate) fn is_included(&self, target: &Target) -> bool {
+        self.target_filter.as_ref().is_none_or(|filter| filter(target))
+    }
+}
+
+impl fmt::Debug for GeneratorOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratorOptions")
+            .field("output_dir", &self.output_dir)
+            .field("project_name", &self.project_name)
+            .field("platforms", &self.platforms)
+            .field("configurations", &self.configurations)
+            .field("features", &self.features)
+            .field("package_filter", &self.package_filter.is_some())
+            .field("target_filter", &self.target_filter.is_some())
+            .field("id_seed", &self.id_seed)
+            .field("cargo_path", &self.cargo_path)
+            .finish()
+    }
+}
@@ -0,0 +1,76 @@
+//! Mapping of Xcode's SDKs and architectures to Rust target triples
+
+use crate::xcconfig::BuildSetting;
+
+/// How to build Rust code for one Xcode SDK + arch combination
+pub(crate) struct RustTarget {
+    /// Xcode's `PLATFORM_NAME`, used for `[sdk=…*]` build setting conditions
+    pub sdk: &'static str,
+    /// Xcode's `CURRENT_ARCH` pattern, used for `[arch=…]` build setting conditions.
+    /// Catalyst builds may use `x86_64h`.
+    pub arch: &'static str,
+    pub triple: &'static str,
+    /// Tier 3 targets don't have pre-built std in rustup, and need nightly `-Z build-std=…` with these crates
+    pub build_std: Option<&'static str>,
+    /// Overrides panic strategy of the Cargo profile
+    pub panic: Option<&'static str>,
+}
+
+const BUILD_STD: Option<&str> = Some("std,panic_abort");
+
+const fn target(sdk: &'static str, arch: &'static str, triple: &'static str, build_std: Option<&'static str>) -> RustTarget {
+    RustTarget { sdk, arch, triple, build_std, panic: None }
+}
+
+pub(crate) const RUST_TARGETS: &[RustTarget] = &[
+    target("macosx", "arm64", "aarch64-apple-darwin", None),
+    target("macosx", "x86_64*", "x86_64-apple-darwin", None),
+    target("iphoneos", "arm64", "aarch64-apple-ios", None),
+    target("iphonesimulator", "arm64", "aarch64-apple-ios-sim", None),
+    target("iphonesimulator", "x86_64*", "x86_64-apple-ios", None),
+    target("appletvos", "arm64", "aarch64-apple-tvos", BUILD_STD),
+    target("appletvsimulator", "arm64", "aarch64-apple-tvos-sim", BUILD_STD),
+    target("appletvsimulator", "x86_64*", "x86_64-apple-tvos", BUILD_STD),
+    target("watchos", "arm64_32", "arm64_32-apple-watchos", BUILD_STD),
+    target("watchos", "armv7k", "armv7k-apple-watchos", BUILD_STD),
+    target("watchos", "arm64", "aarch64-apple-watchos", BUILD_STD),
+    target("watchsimulator", "arm64", "aarch64-apple-watchos-sim", BUILD_STD),
+    target("watchsimulator", "x86_64*", "x86_64-apple-watchos-sim", BUILD_STD),
+    target("xros", "arm64", "aarch64-apple-visionos", BUILD_STD),
+    target("xrsimulator", "arm64", "aarch64-apple-visionos-sim", BUILD_STD),
+];
+
+/// Mac Catalyst uses the `macosx` SDK, so it can't be told apart by build setting conditions.
+/// The build script switches to these when `IS_MACCATALYST` is set.
+pub(crate) const MACCATALYST_TARGETS: &[RustTarget] = &[
+    target("macosx", "arm64", "aarch64-apple-ios-macabi", BUILD_STD),
+    target("macosx", "x86_64*", "x86_64-apple-ios-macabi", BUILD_STD),
+];
+
+/// Conditional build settings that tell the build script which Rust target to use
+pub(crate) fn target_build_settings() -> Vec<BuildSetting> {
+    let mut out = Vec::new();
+    for (prefix, targets) in [("CARGO_XCODE", RUST_TARGETS), ("CARGO_XCODE_MACCATALYST", MACCATALYST_TARGETS)] {
+        for t in targets {
+            let cond = format!("[sdk={}*][arch={}]", t.sdk, t.arch);
+            out.push(BuildSetting::new(format!("{prefix}_TARGET_TRIPLE{cond}"), t.triple));
+            if let Some(build_std) = t.build_std {
+                out.push(BuildSetting::new(format!("{prefix}_BUILD_STD{cond}"), build_std));
+            }
+            if let Some(panic) = t.panic {
+                out.push(BuildSetting::new(format!("{prefix}_PANIC{cond}"), panic));
+            }
+        }
+        // for building all archs at once
+        let mut sdks_seen = Vec::new();
+        for t in targets {
+            if sdks_seen.contains(&t.sdk) {
+                continue;
+            }
+            sdks_seen.push(t.sdk);
+            let triples = targets.iter().filter(|o| o.sdk == t.sdk).map(|o| format!("{}:{}", o.arch, o.triple)).collect::<Vec<_>>();
+            out.push(BuildSetting::new(format!("{prefix}_TARGET_TRIPLES[sdk={}*]", t.sdk), triples.join(" ")));
+        }
+    }
+    out
+}
@@ -0,0 +1,45 @@
+//! Summary of generated projects for scripts, e.g. `--message-format=json`
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ProjectReport {
+    /// Cargo package name
+    pub package: String,
+    /// The `.xcodeproj` dir
+    pub path: PathBuf,
+    /// ID of the `PBXProject` object
+    pub project_id: String,
+    pub targets: Vec<TargetReport>,
+    /// Project files that have been written. Unchanged files aren't rewritten, and aren't listed.
+    pub files_written: Vec<PathBuf>,
+    /// Generated files of configurations that have been removed
+    pub files_removed: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct TargetReport {
+    /// Name of the Xcode target
+    pub name: String,
+    /// Name of the Cargo target
+    pub cargo_target: String,
+    /// Cargo crate type: `bin`, `staticlib` or `cdylib`
+    pub kind: String,
+    /// Xcode's `PRODUCT_NAME`
+    pub product_name: String,
+    /// File name of the product in Xcode's build dir, e.g. `libfoo_static.a`
+    pub file_name: String,
+    /// File name of the product in Cargo's target dir, e.g. `libfoo.a`
+    pub cargo_file_name: String,
+    /// Xcode's `productType`, e.g. `com.apple.product-type.library.static`
+    pub product_type: String,
+    /// Xcode's `SUPPORTED_PLATFORMS`
+    pub supported_platforms: Vec<String>,
+    /// ID of the `PBXNativeTarget` object, for target dependencies in other projects
+    pub target_id: String,
+    /// ID of the product's `PBXFileReference`, for linking it in other projects
+    pub product_id: String,
+}
@@ -0,0 +1,57 @@
+//! `.xcconfig` files, which can be overridden without editing the generated project
+
+pub(crate) struct BuildSetting {
+    /// May include conditions, e.g. `FOO[sdk=macosx*]`
+    pub name: String,
+    pub value: String,
+    pub comment: Option<&'static str>,
+}
+
+impl BuildSetting {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self { name: name.into(), value: value.into(), comment: None }
+    }
+
+    pub fn with_comment(mut self, comment: &'static str) -> Self {
+        self.comment = Some(comment);
+        self
+    }
+}
+
+/// Lines of `NAME = value`. Xcconfig values aren't quoted, and `//` starts a comment.
+pub(crate) fn render(settings: &[BuildSetting]) -> String {
+    settings.iter().map(|s| {
+        let value = escape_comments(&s.value);
+        match s.comment {
+            Some(comment) => format!("{} = {value} // {comment}\n", s.name),
+            None => format!("{} = {value}\n", s.name),
+        }
+    }).collect()
+}
+
+/// `$()` expands to nothing, so `/$()/` is `//` that isn't a comment, e.g. in URLs
+fn escape_comments(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut prev_slash = false;
+    for c in value.chars() {
+        if c == '/' && prev_slash {
+            out.push_str("$()");
+        }
+        prev_slash = c == '/';
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_in_values() {
+        assert_eq!(escape_comments("https://example.com/a"), "https:/$()/example.com/a");
+        assert_eq!(escape_comments("a///b"), "a/$()/$()/b");
+        let settings = [BuildSetting::new("URL", "x//y").with_comment("note")];
+        assert_eq!(render(&settings), "URL = x/$()/y // note\n");
+    }
+}
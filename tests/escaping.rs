@@ -0,0 +1,93 @@
+//! Generates a project for a crate in a dir with characters that need escaping in pbxproj, xcconfig, xcfilelist and shell scripts
+
+use cargo_xcode::{Generator, GeneratorOptions};
+use std::fs;
+
+const MANIFEST: &str = r#"[package]
+name = "escaping"
+version = "0.1.0"
+edition = "2021"
+
+[workspace]
+
+[lib]
+crate-type = ["staticlib"]
+
+[[bin]]
+name = "my tool$x"
+path = "src/main.rs"
+required-features = ["a", "b"]
+
+[features]
+a = []
+b = []
+
+[package.metadata.xcode]
+extra-args = ["--config", "it's"]
+env = { URL = "https://example.com/$x" }
+"#;
+
+#[test]
+fn special_characters_in_paths_and_names() {
+    let base = std::env::temp_dir().join(format!("cargo-xcode-escaping-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    let crate_dir = base.join("my $crate 'é");
+    let output_dir = base.join("out \"dir\"");
+    fs::create_dir_all(crate_dir.join("src")).unwrap();
+    fs::write(crate_dir.join("Cargo.toml"), MANIFEST).unwrap();
+    fs::write(crate_dir.join("src/lib.rs"), "").unwrap();
+    fs::write(crate_dir.join("src/main.rs"), "fn main() {}").unwrap();
+
+    let metadata = cargo_metadata::MetadataCommand::new().manifest_path(crate_dir.join("Cargo.toml")).no_deps().exec().unwrap();
+    let generators = Generator::from_metadata(&metadata, GeneratorOptions::new().output_dir(&output_dir));
+    assert_eq!(generators.len(), 1);
+    let report = generators[0].write_project().unwrap();
+    assert_eq!(report.path, output_dir.join("escaping.xcodeproj"));
+    // after the files it references
+    assert_eq!(report.files_written.last(), Some(&report.path.join("project.pbxproj")));
+
+    let read = |name: &str| fs::read_to_string(report.path.join(name)).unwrap();
+    let pbxproj = read("project.pbxproj");
+    assert_contains(&pbxproj, r#"COMPILER_FLAGS = "--bin 'my tool$(DOLLAR)x' --features a,b";"#);
+    assert_contains(&pbxproj, r#"PRODUCT_NAME = "my tool$(DOLLAR)x";"#);
+    assert_contains(&pbxproj, r#""CARGO_XCODE_CARGO_FILE_NAME" = "my tool$(DOLLAR)x";"#);
+    assert_contains(&pbxproj, r#"path = "../my $crate 'é/Cargo.toml";"#);
+    assert_balanced_quotes(&pbxproj);
+
+    let xcconfig = read("CargoXcode.xcconfig");
+    assert_contains(&xcconfig, r"CARGO_XCODE_EXTRA_ARGS = --config 'it'\''s' //");
+    assert_contains(&xcconfig, "CARGO_XCODE_ENV = URL='https:/$()/example.com/$(DOLLAR)x' //");
+
+    let input_files = read("CargoInputs.xcfilelist");
+    assert_eq!(input_files.lines().collect::<Vec<_>>(), [
+        "$(PROJECT_DIR)/../my $(DOLLAR)crate 'é/Cargo.toml",
+        "$(PROJECT_DIR)/../my $(DOLLAR)crate 'é/src/lib.rs",
+        "$(PROJECT_DIR)/../my $(DOLLAR)crate 'é/src/main.rs",
+    ]);
+
+    // unchanged files aren't rewritten
+    assert!(generators[0].write_project().unwrap().files_written.is_empty());
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[track_caller]
+fn assert_contains(haystack: &str, needle: &str) {
+    assert!(haystack.contains(needle), "missing {needle}");
+}
+
+/// Unescaped `"` would end a string early, and leave the rest of the line unquoted
+#[track_caller]
+fn assert_balanced_quotes(pbxproj: &str) {
+    for line in pbxproj.lines() {
+        let mut quotes = 0;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => { chars.next(); },
+                '"' => quotes += 1,
+                _ => {},
+            }
+        }
+        assert_eq!(quotes % 2, 0, "unbalanced quotes in {line}");
+    }
+}
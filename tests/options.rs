@@ -0,0 +1,47 @@
+use cargo_xcode::{Generator, GeneratorOptions};
+
+fn this_crate() -> cargo_metadata::Metadata {
+    cargo_metadata::MetadataCommand::new().manifest_path(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).no_deps().exec().unwrap()
+}
+
+#[test]
+fn platforms_skip_macos_only_targets() {
+    let generators = Generator::from_metadata(&this_crate(), GeneratorOptions::new().platforms(["iphoneos"]));
+    assert_eq!(generators.len(), 1);
+    let report = generators[0].report().unwrap();
+    assert!(!report.targets.is_empty());
+    for target in &report.targets {
+        assert_eq!(target.kind, "staticlib");
+        assert_eq!(target.supported_platforms, ["iphoneos"]);
+    }
+
+    let only_bins = GeneratorOptions::new().platforms(["iphoneos"]).target_filter(|t| t.kind.iter().any(|k| k == "bin"));
+    assert!(Generator::from_metadata(&this_crate(), only_bins).is_empty());
+}
+
+#[test]
+fn relative_output_dir() {
+    let generators = Generator::from_metadata(&this_crate(), GeneratorOptions::new().output_dir("target/xcode"));
+    let report = generators[0].report().unwrap();
+    assert!(report.path.is_absolute());
+    let input_files = generators[0].input_file_list().unwrap();
+    assert!(input_files.lines().any(|line| line == "$(PROJECT_DIR)/../../Cargo.toml"), "{input_files}");
+    assert!(input_files.lines().all(|line| line.starts_with("$(PROJECT_DIR)/../../") && !line.contains("//")), "{input_files}");
+}
+
+#[test]
+fn workspace_target_dir_with_relative_output_dir() {
+    let base = std::env::temp_dir().join(format!("cargo-xcode-target-dir-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("src")).unwrap();
+    std::fs::write(base.join("Cargo.toml"), "[package]\nname = \"shared\"\nversion = \"0.1.0\"\n\n[workspace]\n\n[lib]\ncrate-type = [\"staticlib\"]\n\n[package.metadata.xcode]\ntarget-dir = \"workspace\"\n").unwrap();
+    std::fs::write(base.join("src/lib.rs"), "").unwrap();
+    let metadata = cargo_metadata::MetadataCommand::new().manifest_path(base.join("Cargo.toml")).no_deps().exec().unwrap();
+
+    let output_dir = pathdiff::diff_paths(base.join("xcode"), std::env::current_dir().unwrap()).unwrap();
+    assert!(output_dir.is_relative());
+    let generators = Generator::from_metadata(&metadata, GeneratorOptions::new().output_dir(output_dir));
+    let xcconfig = generators[0].xcconfig_files().unwrap().into_iter().find(|(name, _)| name == "CargoXcode.xcconfig").unwrap().1;
+    assert!(xcconfig.contains("CARGO_TARGET_DIR = $(PROJECT_DIR)/../target //"), "{xcconfig}");
+    std::fs::remove_dir_all(&base).unwrap();
+}